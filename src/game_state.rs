@@ -1,9 +1,11 @@
 mod player;
 pub mod terrain;
+pub mod time_of_day;
 mod load_store;
 
 use crate::game_state::player::Player;
 use crate::game_state::terrain::Terrain;
+use crate::game_state::time_of_day::TimeOfDay;
 use crate::graphics::Graphics;
 use crate::input_state::InputState;
 use crate::settings::Settings;
@@ -13,6 +15,7 @@ use nalgebra::Vector3;
 pub struct GameState {
     pub player: Player,
     pub terrain: Terrain,
+    pub time_of_day: TimeOfDay,
 }
 
 impl GameState {
@@ -24,24 +27,33 @@ impl GameState {
 
         PushConstants {
             cam_transform: cam_transform.into(),
+            target_block: [0; 4].into(),
+            placement_block: [0; 4].into(),
+            sun_direction: self.time_of_day.sun_direction(),
+            frame: 0,
+            jitter: [0.; 2].into(),
+            accumulated_frames: 0,
         }
     }
 
-    pub fn new() -> Self {
+    pub fn new(chunk_size: u32) -> Self {
         Self {
             player: Player::new(),
-            terrain: Terrain::empty(),
+            terrain: Terrain::empty(chunk_size),
+            time_of_day: TimeOfDay::new(),
         }
     }
     pub fn update(&mut self, input_state: &InputState, settings: &Settings, delta_time: f32) {
         self.player.pan(input_state, settings);
         self.player.movement(input_state, delta_time);
+        self.player.select_block(input_state);
+        self.time_of_day.update(input_state, delta_time);
     }
 
     pub fn get_player_chunk(&self) -> Vector3<i32> {
         self.player
             .position
-            .map(|x: f32| (x / Graphics::CHUNK_SIZE as f32).floor() as i32)
+            .map(|x: f32| (x / self.terrain.chunk_size as f32).floor() as i32)
     }
     pub fn save(&self, file_name: &str) {
         self.terrain.save_terrain(file_name);
@@ -50,6 +62,7 @@ impl GameState {
         Self{
             player: Player::new(),
             terrain: Terrain::load(graphics, file_name),
+            time_of_day: TimeOfDay::new(),
         }
     }
 }
@@ -59,7 +72,7 @@ mod tests {
 
     #[test]
     fn test_get_player_chunk() {
-        let mut game_state = GameState::new();
+        let mut game_state = GameState::new(32);
         // Test case 1: Player at origin
         game_state.player.position = Vector3::new(0.0, 0.0, 0.0);
         assert_eq!(game_state.get_player_chunk(), Vector3::new(0, 0, 0));
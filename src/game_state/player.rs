@@ -1,3 +1,7 @@
+use crate::game_state::terrain::block::Block;
+use crate::game_state::terrain::block::emissive_block::EmissiveBlock;
+use crate::game_state::terrain::block::solid_block::SolidBlock;
+use crate::game_state::terrain::block::transparent_block::TransparentBlock;
 use crate::input_state::{InputState, PressState};
 use crate::settings::Settings;
 use nalgebra::{Rotation3, Vector3};
@@ -6,14 +10,71 @@ use winit::event::VirtualKeyCode;
 pub struct Player {
     pub position: Vector3<f32>,
     pub rotation: Rotation,
+    pub hotbar: [Block; Player::HOTBAR_SIZE],
+    pub selected_slot: usize,
 }
 
 impl Player {
     const SPEED: f32 = 25.;
+    pub const HOTBAR_SIZE: usize = 7;
+    const HOTBAR_KEYS: [VirtualKeyCode; Player::HOTBAR_SIZE] = [
+        VirtualKeyCode::Key1,
+        VirtualKeyCode::Key2,
+        VirtualKeyCode::Key3,
+        VirtualKeyCode::Key4,
+        VirtualKeyCode::Key5,
+        VirtualKeyCode::Key6,
+        VirtualKeyCode::Key7,
+    ];
     pub fn new() -> Self {
         Self {
             position: Vector3::<f32>::new(16., 16., 16.),
             rotation: Rotation::zero(),
+            hotbar: [
+                Block::SolidBlock(SolidBlock::Stone),
+                Block::SolidBlock(SolidBlock::Grass),
+                Block::SolidBlock(SolidBlock::Gravel),
+                Block::TransparentBlock(TransparentBlock::Glass),
+                Block::TransparentBlock(TransparentBlock::Water),
+                Block::EmissiveBlock(EmissiveBlock::Lamp),
+                Block::EmissiveBlock(EmissiveBlock::Lava),
+            ],
+            selected_slot: 0,
+        }
+    }
+
+    /// The block that gets placed on left click
+    pub fn selected_block(&self) -> Block {
+        self.hotbar[self.selected_slot]
+    }
+
+    /// Selects a hotbar slot with the number keys or the mouse wheel
+    pub fn select_block(&mut self, input_state: &InputState) {
+        if let Some(slot) = Self::HOTBAR_KEYS
+            .iter()
+            .position(|key| input_state.is_key_pressed(*key, PressState::Down))
+        {
+            self.selected_slot = slot;
+        }
+        let scroll_steps = input_state.mouse.scroll_steps;
+        if scroll_steps != 0 {
+            self.scroll_selection(-scroll_steps);
+        }
+    }
+
+    fn scroll_selection(&mut self, steps: i32) {
+        self.selected_slot =
+            (self.selected_slot as i32 + steps).rem_euclid(Self::HOTBAR_SIZE as i32) as usize;
+    }
+
+    /// Selects the slot holding `block`, or puts it into the selected slot if it is not in the hotbar
+    pub fn pick_block(&mut self, block: Block) {
+        if block == Block::Air {
+            return;
+        }
+        match self.hotbar.iter().position(|x| *x == block) {
+            Some(slot) => self.selected_slot = slot,
+            None => self.hotbar[self.selected_slot] = block,
         }
     }
 
@@ -85,3 +146,23 @@ impl Rotation {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hotbar_selection() {
+        let mut player = Player::new();
+        player.scroll_selection(-1);
+        assert_eq!(player.selected_slot, Player::HOTBAR_SIZE - 1);
+        player.scroll_selection(1);
+        assert_eq!(player.selected_slot, 0);
+
+        player.pick_block(Block::TransparentBlock(TransparentBlock::Glass));
+        assert_eq!(player.selected_block(), Block::TransparentBlock(TransparentBlock::Glass));
+
+        player.pick_block(Block::Air);
+        assert_eq!(player.selected_block(), Block::TransparentBlock(TransparentBlock::Glass));
+    }
+}
@@ -1,125 +1,425 @@
 use crate::game_state::terrain::block::Block;
+use crate::game_state::terrain::chunk_cache::ChunkCache;
+use crate::game_state::terrain::chunk_data::{coarse_block_of_cube, ChunkData};
 use crate::graphics::Graphics;
+use crate::graphics::terrain_functionality::lod_chunk_containing;
 use nalgebra::Vector3;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::{self, Read, Write};
 use vulkano::buffer::Subbuffer;
 use crate::game_state::load_store::Serializeable;
 use crate::graphics;
 
 pub mod block;
+pub mod chunk_cache;
+pub mod chunk_data;
 pub struct Terrain {
-    pub chunks: HashMap<Vector3<i32>, ChunkBuffer>,
+    pub chunks: HashMap<Vector3<i32>, Chunk>,
+    /// chunks of the coarser levels of detail by level and position, see `build_lod_chunk`
+    pub lod_chunks: HashMap<(u32, Vector3<i32>), Chunk>,
+    pub chunk_size: u32,
+    cache: ChunkCache,
+    /// block changes waiting for `apply_block_edits`, at most one per block
+    pending_edits: HashMap<Vector3<i32>, Block>,
+    /// block changes of chunks that weren't resident, by chunk, applied once the chunk is loaded
+    deferred_edits: HashMap<Vector3<i32>, HashMap<Vector3<i32>, Block>>,
+}
+pub type ChunkBuffer = Subbuffer<[u16]>;
+
+pub enum Chunk {
+    /// Every block of the chunk has this id, so no host buffer is allocated for it.
+    Uniform(u16),
+    Data(ChunkBuffer),
+}
+
+impl Chunk {
+    /// Uploads the blocks into a host buffer unless they are all the same.
+    pub fn from_chunk_data(graphics: &mut Graphics, chunk_data: ChunkData) -> Self {
+        match chunk_data.uniform_block() {
+            Some(block) => Chunk::Uniform(block),
+            None => Chunk::Data(graphics.chunk_from_data(chunk_data)),
+        }
+    }
+
+    pub fn to_chunk_data(&self, chunk_size: u32) -> ChunkData {
+        match self {
+            Chunk::Uniform(block) => ChunkData::filled(chunk_size, *block),
+            Chunk::Data(buffer) => ChunkData::new(chunk_size, (&*buffer.read().unwrap()).into()),
+        }
+    }
 }
-pub type ChunkData = [u16; Graphics::CHUNK_VOLUME as usize];
-pub type ChunkBuffer = Subbuffer<ChunkData>;
 
 impl Terrain {
-    pub fn empty() -> Self {
+    pub fn empty(chunk_size: u32) -> Self {
         Self {
             chunks: HashMap::new(),
+            lod_chunks: HashMap::new(),
+            chunk_size,
+            cache: ChunkCache::new(),
+            pending_edits: HashMap::new(),
+            deferred_edits: HashMap::new(),
         }
     }
-    /// Checks whether the chunk is present in the terrain struct and generates it otherwise.
-    pub fn upload_chunk(&mut self, graphics: &mut Graphics, chunk_position: Vector3<i32>) {
+    /// Generates the chunk unless it is resident or can be read back from the swap directory.
+    /// Returns the blocks changed by edits that were deferred until the chunk is resident.
+    fn generate_chunk_if_missing(&mut self, graphics: &mut Graphics, chunk_position: Vector3<i32>) -> Vec<Vector3<i32>> {
+        let mut edited_blocks = Vec::new();
         if !self.chunks.contains_key(&chunk_position) {
-            let chunk = graphics.generate_chunk(chunk_position);
+            let swapped_chunk = self.load_swapped_chunk(graphics, chunk_position).unwrap_or_else(|error| {
+                println!("Failed to read back chunk {}, generating it instead: {}", chunk_position, error);
+                None
+            });
+            let mut chunk = swapped_chunk.unwrap_or_else(|| graphics.generate_chunk(0, chunk_position));
 
+            if self.deferred_edits.contains_key(&chunk_position) {
+                let mut chunk_data = chunk.to_chunk_data(self.chunk_size);
+                edited_blocks = self.apply_deferred_edits(&mut chunk_data, chunk_position);
+                self.deferred_edits.remove(&chunk_position);
+                chunk = Chunk::from_chunk_data(graphics, chunk_data);
+                self.cache.mark_dirty(chunk_position);
+            }
             self.chunks.insert(chunk_position, chunk);
         }
+        self.cache.touch(chunk_position);
+        edited_blocks
+    }
+
+    /// Writes the deferred edits of a chunk into its blocks and returns the edited block positions.
+    fn apply_deferred_edits(&self, chunk_data: &mut ChunkData, chunk_position: Vector3<i32>) -> Vec<Vector3<i32>> {
+        let Some(edits) = self.deferred_edits.get(&chunk_position) else {
+            return Vec::new();
+        };
+        for (block_position, block_type) in edits {
+            chunk_data.blocks_mut()[graphics::block_in_chunk_index(*block_position, self.chunk_size)] = block_type.as_u16();
+        }
+        edits.keys().copied().collect()
+    }
+
+    /// Checks whether the chunk is present in the terrain struct and generates it otherwise.
+    pub fn upload_chunk(&mut self, graphics: &mut Graphics, chunk_position: Vector3<i32>) {
+        let edited_blocks = self.generate_chunk_if_missing(graphics, chunk_position);
 
         let chunk = self.chunks.get(&chunk_position).unwrap();
 
         let chunk_index = graphics::chunk_buffer_index(chunk_position, &graphics.settings);
-        graphics.copy_buffer_to_image(
-            chunk.clone(),
-            graphics.render_core.buffers.block_data_buffers[chunk_index].clone(),
-            None,
-        );
-        graphics.generate_distance_field(chunk_position);
-    }
-    pub fn place_block(
-        &mut self,
-        graphics: &mut Graphics,
-        block_position: Vector3<i32>,
-        block_type: Block,
-    ) {
+        graphics.set_chunk_info(chunk_index, chunk);
+        graphics.set_chunk_lights(chunk_index, chunk);
+        if let Chunk::Data(buffer) = chunk {
+            graphics.copy_buffer_to_image(
+                buffer.clone(),
+                graphics.render_core.buffers.block_data_buffers[chunk_index].clone(),
+                None,
+            );
+        }
+        // levels of detail built before the chunk was resident don't contain its deferred edits
+        if !edited_blocks.is_empty() {
+            self.refresh_lod_chunks(graphics, &HashMap::from([(chunk_position, edited_blocks)]));
+        }
+    }
+
+    /// Builds a chunk of the given level of detail unless it is already built, by downsampling the full resolution
+    /// chunks inside of it that are resident or swapped out. Only where there are none it falls back to the generator
+    /// at the scale of the level, since generating all 8^level full resolution chunks would be far too slow.
+    fn build_lod_chunk(&mut self, graphics: &mut Graphics, level: u32, lod_chunk_position: Vector3<i32>) {
+        if self.lod_chunks.contains_key(&(level, lod_chunk_position)) {
+            return;
+        }
+        let stored_chunks = self
+            .chunks
+            .keys()
+            .chain(self.swapped_chunk_positions())
+            .copied()
+            .filter(|chunk_position| lod_chunk_containing(*chunk_position, level) == lod_chunk_position)
+            .collect::<Vec<_>>();
+        if stored_chunks.is_empty() {
+            let chunk = graphics.generate_chunk(level, lod_chunk_position);
+            self.lod_chunks.insert((level, lod_chunk_position), chunk);
+            return;
+        }
+
+        let mut lod_chunk_data = if stored_chunks.len() == 1 << (3 * level) {
+            ChunkData::filled(self.chunk_size, 0)
+        } else {
+            graphics.generate_chunk(level, lod_chunk_position).to_chunk_data(self.chunk_size)
+        };
+        for chunk_position in stored_chunks {
+            if let Err(error) = self.downsample_chunk(lod_chunk_data.blocks_mut(), level, chunk_position) {
+                println!("Failed to read back chunk {} for level of detail {}: {}", chunk_position, level, error);
+            }
+        }
+        let chunk = Chunk::from_chunk_data(graphics, lod_chunk_data);
+        self.lod_chunks.insert((level, lod_chunk_position), chunk);
+    }
+
+    /// Writes the blocks of the given level of detail covered by a resident or swapped out chunk into the blocks of
+    /// the chunk of that level containing it.
+    fn downsample_chunk(&self, lod_blocks: &mut [u16], level: u32, chunk_position: Vector3<i32>) -> io::Result<()> {
+        let cube_size = (1 << level).min(self.chunk_size) as usize;
+        // a uniform chunk stands for its block at every level, so it doesn't need to be expanded
+        let uniform_block = match self.chunks.get(&chunk_position) {
+            Some(Chunk::Uniform(block)) => Some(*block),
+            _ => None,
+        };
+        let chunk_data = match uniform_block {
+            Some(_) => None,
+            None => self.stored_chunk_data(chunk_position)?,
+        };
+        for z in (0..self.chunk_size as i32).step_by(cube_size) {
+            for y in (0..self.chunk_size as i32).step_by(cube_size) {
+                for x in (0..self.chunk_size as i32).step_by(cube_size) {
+                    let block_position = chunk_position * self.chunk_size as i32 + Vector3::new(x, y, z);
+                    let (lod_block_position, block) = match &chunk_data {
+                        Some(chunk_data) => lod_block(chunk_data.blocks(), self.chunk_size, level, block_position),
+                        None => (block_position.map(|x| x.div_euclid(1 << level)), uniform_block.unwrap()),
+                    };
+                    lod_blocks[graphics::block_in_chunk_index(lod_block_position, self.chunk_size)] = block;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn upload_lod_chunk(&mut self, graphics: &mut Graphics, level: u32, lod_chunk_position: Vector3<i32>) {
+        self.build_lod_chunk(graphics, level, lod_chunk_position);
+        let chunk = &self.lod_chunks[&(level, lod_chunk_position)];
+
         graphics.wait_and_reset_last_frame_end();
-        let block_chunk =
-            block_position.map(|x| (x as f32 / Graphics::CHUNK_SIZE as f32).floor() as i32);
-        let chunk = self.chunks.get_mut(&block_chunk).unwrap();
-        let mut guard = chunk.write().unwrap();
-        guard[graphics::block_in_chunk_index(block_position)] = block_type.as_u16();
-        drop(guard);
-        let index = graphics::chunk_buffer_index(block_chunk, &graphics.settings);
-
-        graphics.copy_buffer_to_image(
-            chunk.clone(),
-            graphics.render_core.buffers.block_data_buffers[index].clone(),
-            Some(block_position),
-        );
+        let chunk_index = graphics::lod_chunk_buffer_index(level, lod_chunk_position, &graphics.settings);
+        graphics.set_lod_chunk_info(chunk_index, chunk);
+        if let Chunk::Data(buffer) = chunk {
+            graphics.copy_buffer_to_image(
+                buffer.clone(),
+                graphics.render_core.buffers.lod_block_data_buffers[chunk_index].clone(),
+                None,
+            );
+        }
+    }
+
+    /// Recomputes the blocks of the coarser levels of detail that contain the edited blocks, given by chunk,
+    /// and uploads them if they are loaded. Levels of detail that aren't built yet pick the edits up when they are.
+    fn refresh_lod_chunks(&mut self, graphics: &mut Graphics, edited_blocks: &HashMap<Vector3<i32>, Vec<Vector3<i32>>>) {
+        for level in 1..graphics.settings.graphics_settings.level_of_detail_layers as u32 {
+            let mut changed_blocks: HashMap<Vector3<i32>, Vec<Vector3<i32>>> = HashMap::new();
+            // uniform chunks have never been uploaded, so they need a full copy after getting a buffer
+            let mut converted_chunks = HashSet::new();
+            for (block_chunk, blocks) in edited_blocks {
+                let lod_chunk_position = lod_chunk_containing(*block_chunk, level);
+                let Some(lod_chunk) = self.lod_chunks.get_mut(&(level, lod_chunk_position)) else {
+                    continue;
+                };
+                // edited chunks always have a buffer, since a uniform chunk gets one when a block of it changes
+                let Some(Chunk::Data(buffer)) = self.chunks.get(block_chunk) else {
+                    continue;
+                };
+                let chunk_blocks = buffer.read().unwrap();
+                for block_position in blocks {
+                    let (lod_block_position, block) = lod_block(&chunk_blocks, self.chunk_size, level, *block_position);
+                    if let Chunk::Uniform(uniform_block) = *lod_chunk {
+                        if uniform_block == block {
+                            continue;
+                        }
+                        *lod_chunk = Chunk::Data(graphics.chunk_from_data(ChunkData::filled(self.chunk_size, uniform_block)));
+                        converted_chunks.insert(lod_chunk_position);
+                    }
+                    let Chunk::Data(lod_buffer) = lod_chunk else {
+                        unreachable!()
+                    };
+                    lod_buffer.write().unwrap()[graphics::block_in_chunk_index(lod_block_position, self.chunk_size)] = block;
+                    changed_blocks.entry(lod_chunk_position).or_default().push(lod_block_position);
+                }
+            }
+
+            for (lod_chunk_position, blocks) in changed_blocks {
+                if !graphics.is_lod_chunk_loaded(level, lod_chunk_position) {
+                    continue;
+                }
+                let chunk = &self.lod_chunks[&(level, lod_chunk_position)];
+                let index = graphics::lod_chunk_buffer_index(level, lod_chunk_position, &graphics.settings);
+                graphics.set_lod_chunk_info(index, chunk);
+                let Chunk::Data(buffer) = chunk else {
+                    unreachable!()
+                };
+                graphics.copy_buffer_to_image(
+                    buffer.clone(),
+                    graphics.render_core.buffers.lod_block_data_buffers[index].clone(),
+                    if converted_chunks.contains(&lod_chunk_position) { None } else { Some(blocks.as_slice()) },
+                );
+            }
+        }
+    }
+
+    /// Regenerates the distance field of a loaded chunk, uniform chunks don't have one.
+    pub fn update_distance_field(&self, graphics: &mut Graphics, chunk_position: Vector3<i32>) {
+        if let Some(Chunk::Data(_)) = self.chunks.get(&chunk_position)
+            && graphics.is_chunk_loaded(chunk_position)
+        {
+            graphics.generate_distance_field(chunk_position);
+        }
+    }
+
+    /// Queues a block change, it takes effect with the next `apply_block_edits`.
+    /// A later change of the same block replaces it.
+    pub fn place_block(&mut self, block_position: Vector3<i32>, block_type: Block) {
+        self.pending_edits.insert(block_position, block_type);
+    }
+
+    /// Applies the queued block changes with one copy per edited chunk and updates the distance fields once.
+    /// Few edits update the distances around each block, more rebuild every affected chunk once.
+    /// Changes of chunks that aren't resident are deferred until chunk loading brings them in.
+    pub fn apply_block_edits(&mut self, graphics: &mut Graphics) {
+        const LOCAL_DISTANCE_UPDATE_LIMIT: usize = 4;
+        if self.pending_edits.is_empty() {
+            return;
+        }
         graphics.wait_and_reset_last_frame_end();
-        graphics.generate_distance_field(block_chunk);
+        graphics.reset_accumulation();
+
+        let mut edited_blocks: HashMap<Vector3<i32>, Vec<Vector3<i32>>> = HashMap::new();
+        // uniform chunks have never been uploaded, so they need a full copy after getting a buffer
+        let mut converted_chunks = HashSet::new();
+        for (block_position, block_type) in std::mem::take(&mut self.pending_edits) {
+            let block_chunk = block_position.map(|x| x.div_euclid(self.chunk_size as i32));
+            let Some(chunk) = self.chunks.get_mut(&block_chunk) else {
+                self.deferred_edits.entry(block_chunk).or_default().insert(block_position, block_type);
+                continue;
+            };
+            if let Chunk::Uniform(block) = *chunk {
+                if block == block_type.as_u16() {
+                    continue;
+                }
+                *chunk = Chunk::Data(graphics.chunk_from_data(ChunkData::filled(self.chunk_size, block)));
+                converted_chunks.insert(block_chunk);
+            }
+            let Chunk::Data(buffer) = chunk else {
+                unreachable!()
+            };
+            buffer.write().unwrap()[graphics::block_in_chunk_index(block_position, self.chunk_size)] = block_type.as_u16();
+            edited_blocks.entry(block_chunk).or_default().push(block_position);
+        }
+
+        for (block_chunk, blocks) in edited_blocks.iter() {
+            self.cache.mark_dirty(*block_chunk);
+            // chunks outside of the loaded area get uploaded with their edits once they are loaded
+            if !graphics.is_chunk_loaded(*block_chunk) {
+                continue;
+            }
+            let chunk = &self.chunks[block_chunk];
+            let Chunk::Data(buffer) = chunk else {
+                unreachable!()
+            };
+            let index = graphics::chunk_buffer_index(*block_chunk, &graphics.settings);
+            if converted_chunks.contains(block_chunk) {
+                graphics.set_chunk_info(index, chunk);
+            }
+            graphics.copy_buffer_to_image(
+                buffer.clone(),
+                graphics.render_core.buffers.block_data_buffers[index].clone(),
+                if converted_chunks.contains(block_chunk) { None } else { Some(blocks.as_slice()) },
+            );
+            graphics.set_chunk_lights(index, chunk);
+        }
+
+        let edit_count = edited_blocks.values().map(Vec::len).sum::<usize>();
+        if edit_count <= LOCAL_DISTANCE_UPDATE_LIMIT {
+            // a chunk that was uniform never had a distance field the local update could build on
+            for block_chunk in converted_chunks {
+                self.update_distance_field(graphics, block_chunk);
+            }
+            // an edit just outside of the loaded area still reaches into the padding of the loaded chunks
+            for block_position in edited_blocks.values().flatten() {
+                if self
+                    .chunks_with_distance_to(*block_position)
+                    .iter()
+                    .any(|chunk_position| graphics.is_chunk_loaded(*chunk_position))
+                {
+                    graphics.update_distance_field_around(*block_position);
+                }
+            }
+        } else {
+            let affected_chunks = edited_blocks
+                .values()
+                .flatten()
+                .flat_map(|block_position| self.chunks_with_distance_to(*block_position))
+                .collect::<HashSet<_>>();
+            for chunk_position in affected_chunks {
+                self.update_distance_field(graphics, chunk_position);
+            }
+        }
+
+        self.refresh_lod_chunks(graphics, &edited_blocks);
+    }
+
+    /// Chunks whose padded distance field region contains the block.
+    fn chunks_with_distance_to(&self, block_position: Vector3<i32>) -> Vec<Vector3<i32>> {
+        let reach = Vector3::repeat(Graphics::DISTANCE_FIELD_PADDING as i32 + 1);
+        let chunk_size = self.chunk_size as i32;
+        let first_chunk = (block_position - reach).map(|x| x.div_euclid(chunk_size));
+        let last_chunk = (block_position + reach).map(|x| x.div_euclid(chunk_size));
+        let mut chunks = Vec::new();
+        for x in first_chunk.x..=last_chunk.x {
+            for y in first_chunk.y..=last_chunk.y {
+                for z in first_chunk.z..=last_chunk.z {
+                    chunks.push(Vector3::new(x, y, z));
+                }
+            }
+        }
+        chunks
     }
-    
+
     pub fn save_terrain(&self, name: &str) {
         let mut file = File::create(name).unwrap();
-        let bytes: Vec<u8> = self.save();
+        let bytes: Vec<u8> = self.save().unwrap();
         file.write(&bytes).unwrap();
     }
 
-    fn save(&self) -> Vec<u8> {
+    fn save(&self) -> io::Result<Vec<u8>> {
         let mut bytes = vec!();
-        self.chunks.iter().for_each(|(position, chunk)| {
+        if !self.deferred_edits.is_empty() {
+            println!("Not saving the edits of {} chunks that were never loaded", self.deferred_edits.len());
+        }
+        // evicted chunks that were edited have to be saved as well
+        for position in self.chunks.keys().chain(self.swapped_chunk_positions()) {
             println!("Storing chunk at {}", position);
             println!("as: {}", Vector3::<i32>::deserialize(&*position.serialize()));
             bytes.extend_from_slice(&position.serialize());
-            bytes.extend_from_slice(&ChunkData::serialize(&*chunk.read().unwrap()));
-        });
-        bytes
+            bytes.extend_from_slice(&self.stored_chunk_data(*position)?.unwrap().serialize());
+        }
+        Ok(bytes)
     }
     pub fn load(graphics: &mut Graphics, file_name: &str) -> Self{
         const POS_BYTES: usize = size_of::<Vector3<i32>>();
-        const CHUNK_BYTES: usize = size_of::<ChunkData>();
+        let chunk_size = graphics.chunk_size();
+        let chunk_bytes = ChunkData::volume(chunk_size) * size_of::<u16>();
         
         let bytes = std::fs::read(file_name).unwrap();
-        let mut terrain = Terrain::empty();
+        let mut terrain = Terrain::empty(chunk_size);
         
-        bytes.chunks(POS_BYTES + CHUNK_BYTES).for_each(|chunk| {
+        bytes.chunks(POS_BYTES + chunk_bytes).for_each(|chunk| {
             let position = Vector3::deserialize(&chunk[0..POS_BYTES]);
-            let chunk_bytes = &chunk[POS_BYTES..POS_BYTES + CHUNK_BYTES];
-            let chunk_data = ChunkData::deserialize(chunk_bytes);
-            terrain.chunks.insert(
-                position,
-                graphics.chunk_from_data(chunk_data)
-            );
+            let chunk_data = ChunkData::deserialize(&chunk[POS_BYTES..]);
+            assert_eq!(chunk_data.size(), chunk_size, "save file was stored with a different chunk size");
+            terrain.chunks.insert(position, Chunk::from_chunk_data(graphics, chunk_data));
+            // loaded chunks can't be regenerated, so they must not be dropped on eviction
+            terrain.cache.mark_dirty(position);
         });
         
         terrain
     }
 }
 
+/// Position of the block of the given level of detail that contains the block and its id, computed from the full
+/// resolution chunk holding the block. Chunks smaller than a block of the level are reduced to a single block.
+fn lod_block(chunk_blocks: &[u16], chunk_size: u32, level: u32, block_position: Vector3<i32>) -> (Vector3<i32>, u16) {
+    let cube_size = (1 << level).min(chunk_size);
+    let origin = block_position.map(|x| x.rem_euclid(chunk_size as i32) as u32 / cube_size * cube_size);
+    let lod_block_position = block_position.map(|x| x.div_euclid(1 << level));
+    (lod_block_position, coarse_block_of_cube(chunk_blocks, chunk_size, origin.into(), cube_size))
+}
 
 
 
 
-impl Serializeable for ChunkData {
-    fn serialize(&self) -> Vec<u8> {
-        let data = self.as_slice().iter().flat_map(
-            |x| x.to_be_bytes()
-        ).collect();
-        data
-    }
-
-    fn deserialize(data: &[u8]) -> Self {
-        let a = data.chunks_exact(2).map(
-            |c|
-                u16::from_be_bytes([c[0], c[1]])
-        ).collect::<Vec<u16>>().try_into().unwrap();
-        a
-    }
-}
 
 impl Serializeable for Vector3<i32> {
     fn serialize(&self) -> Vec<u8> {
@@ -1,29 +1,129 @@
+use crate::game_state::terrain::block::emissive_block::EmissiveBlock;
 use crate::game_state::terrain::block::solid_block::SolidBlock;
 use crate::game_state::terrain::block::transparent_block::TransparentBlock;
+use nalgebra::Vector3;
 
+pub mod emissive_block;
 pub mod solid_block;
 pub mod transparent_block;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Block {
     SolidBlock(SolidBlock),
     TransparentBlock(TransparentBlock),
+    /// solid blocks that light up their surroundings
+    EmissiveBlock(EmissiveBlock),
     Air,
 }
 
+/// Light given off by a block, see `BlockMaterial` in raytrace.glsl
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Emission {
+    pub color: Vector3<f32>,
+    pub intensity: f32,
+}
+
 impl Block {
+    /// Ids of transparent blocks start here, see `is_transparent` in util.glsl
+    pub const FIRST_TRANSPARENT_ID: u16 = 4;
+    /// Ids of emissive blocks start here and end the transparent ones, see `is_emissive` in util.glsl
+    pub const FIRST_EMISSIVE_ID: u16 = 6;
+    /// All block ids are below this
+    pub const ID_COUNT: u16 = 8;
+
     pub fn as_u16(&self) -> u16 {
         match self {
+            Block::Air => 0,
             Block::SolidBlock(SolidBlock::Grass) => 1,
             Block::SolidBlock(SolidBlock::Stone) => 2,
-            Block::TransparentBlock(_) => 3,
-            _ => 0
+            Block::SolidBlock(SolidBlock::Gravel) => 3,
+            Block::TransparentBlock(TransparentBlock::Glass) => 4,
+            Block::TransparentBlock(TransparentBlock::Water) => 5,
+            Block::EmissiveBlock(EmissiveBlock::Lamp) => 6,
+            Block::EmissiveBlock(EmissiveBlock::Lava) => 7,
         }
     }
     pub fn from_u16(value: u16) -> Self {
         match value {
             1 => Block::SolidBlock(SolidBlock::Grass),
             2 => Block::SolidBlock(SolidBlock::Stone),
+            3 => Block::SolidBlock(SolidBlock::Gravel),
+            4 => Block::TransparentBlock(TransparentBlock::Glass),
+            5 => Block::TransparentBlock(TransparentBlock::Water),
+            6 => Block::EmissiveBlock(EmissiveBlock::Lamp),
+            7 => Block::EmissiveBlock(EmissiveBlock::Lava),
             _ => Block::Air,
         }
     }
+
+    pub fn emission(&self) -> Option<Emission> {
+        match self {
+            Block::EmissiveBlock(block) => Some(block.emission()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::mem;
+
+    const ALL_BLOCKS: [Block; 8] = [
+        Block::Air,
+        Block::SolidBlock(SolidBlock::Grass),
+        Block::SolidBlock(SolidBlock::Stone),
+        Block::SolidBlock(SolidBlock::Gravel),
+        Block::TransparentBlock(TransparentBlock::Glass),
+        Block::TransparentBlock(TransparentBlock::Water),
+        Block::EmissiveBlock(EmissiveBlock::Lamp),
+        Block::EmissiveBlock(EmissiveBlock::Lava),
+    ];
+
+    #[test]
+    fn test_all_blocks_listed() {
+        assert_eq!(
+            ALL_BLOCKS.len(),
+            1 + mem::variant_count::<SolidBlock>()
+                + mem::variant_count::<TransparentBlock>()
+                + mem::variant_count::<EmissiveBlock>()
+        );
+        assert_eq!(ALL_BLOCKS.len(), Block::ID_COUNT as usize);
+    }
+
+    #[test]
+    fn test_ids_unique() {
+        let ids = ALL_BLOCKS.iter().map(Block::as_u16).collect::<HashSet<_>>();
+        assert_eq!(ids.len(), ALL_BLOCKS.len());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for block in ALL_BLOCKS {
+            assert_eq!(Block::from_u16(block.as_u16()), block);
+        }
+        for id in 0..=u16::MAX {
+            let block = Block::from_u16(id);
+            assert!(block == Block::Air || block.as_u16() == id);
+        }
+    }
+
+    #[test]
+    fn test_transparent_ids() {
+        for block in ALL_BLOCKS {
+            let transparent = matches!(block, Block::TransparentBlock(_));
+            let id = block.as_u16();
+            assert_eq!((Block::FIRST_TRANSPARENT_ID..Block::FIRST_EMISSIVE_ID).contains(&id), transparent);
+        }
+    }
+
+    #[test]
+    fn test_emissive_ids() {
+        for block in ALL_BLOCKS {
+            let emissive = matches!(block, Block::EmissiveBlock(_));
+            assert_eq!(block.as_u16() >= Block::FIRST_EMISSIVE_ID, emissive);
+            assert_eq!(block.emission().is_some(), emissive);
+        }
+    }
 }
@@ -0,0 +1,23 @@
+use crate::game_state::terrain::block::Emission;
+use nalgebra::Vector3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmissiveBlock {
+    Lamp,
+    Lava,
+}
+
+impl EmissiveBlock {
+    pub fn emission(&self) -> Emission {
+        match self {
+            EmissiveBlock::Lamp => Emission {
+                color: Vector3::new(1., 0.85, 0.6),
+                intensity: 6.,
+            },
+            EmissiveBlock::Lava => Emission {
+                color: Vector3::new(1., 0.35, 0.1),
+                intensity: 3.,
+            },
+        }
+    }
+}
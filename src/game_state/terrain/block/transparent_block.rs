@@ -1,4 +1,24 @@
+use nalgebra::Vector3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum TransparentBlock {
     Glass,
     Water,
 }
+
+impl TransparentBlock {
+    pub fn refractive_index(&self) -> f32 {
+        match self {
+            TransparentBlock::Glass => 1.5,
+            TransparentBlock::Water => 1.33,
+        }
+    }
+
+    /// Fraction of the red, green and blue light absorbed per block travelled through, as the exponent of Beer's law
+    pub fn absorption(&self) -> Vector3<f32> {
+        match self {
+            TransparentBlock::Glass => Vector3::new(0.06, 0.02, 0.05),
+            TransparentBlock::Water => Vector3::new(0.3, 0.08, 0.05),
+        }
+    }
+}
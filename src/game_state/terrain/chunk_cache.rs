@@ -0,0 +1,296 @@
+use crate::game_state::load_store::Serializeable;
+use crate::game_state::terrain::chunk_data::ChunkData;
+use crate::game_state::terrain::{Chunk, Terrain};
+use crate::graphics::Graphics;
+use nalgebra::Vector3;
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts the created caches, so a terrain replacing another one doesn't share its swap directory.
+static CACHE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Bookkeeping for evicting chunks that are no longer loaded, see `Terrain::evict_chunks`.
+pub struct ChunkCache {
+    last_used: HashMap<Vector3<i32>, u64>,
+    tick: u64,
+    /// chunks that differ from what the generator produces and from their swap file
+    dirty: HashSet<Vector3<i32>>,
+    /// chunks whose current blocks are stored in the swap directory
+    swapped: HashSet<Vector3<i32>>,
+    swap_directory: PathBuf,
+    evicted: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TerrainStatistics {
+    pub resident_chunks: usize,
+    /// resident chunks that have a host buffer, the others are uniform
+    pub data_chunks: usize,
+    pub lod_chunks: usize,
+    /// host memory of the stored chunks, including the levels of detail
+    pub resident_bytes: usize,
+    pub swapped_chunks: usize,
+    pub evicted_chunks: usize,
+}
+
+impl ChunkCache {
+    pub fn new() -> Self {
+        Self {
+            last_used: HashMap::new(),
+            tick: 0,
+            dirty: HashSet::new(),
+            swapped: HashSet::new(),
+            swap_directory: std::env::temp_dir().join(format!(
+                "voxel_raytracer_swap_{}_{}",
+                std::process::id(),
+                CACHE_COUNT.fetch_add(1, Ordering::Relaxed)
+            )),
+            evicted: 0,
+        }
+    }
+
+    pub fn touch(&mut self, chunk_position: Vector3<i32>) {
+        self.last_used.insert(chunk_position, self.tick);
+    }
+
+    /// Marks a chunk as changed, so it is written to disk instead of dropped when it gets evicted.
+    pub fn mark_dirty(&mut self, chunk_position: Vector3<i32>) {
+        self.dirty.insert(chunk_position);
+    }
+
+    fn swap_path(&self, chunk_position: Vector3<i32>) -> PathBuf {
+        self.swap_directory
+            .join(format!("{}_{}_{}.chunk", chunk_position.x, chunk_position.y, chunk_position.z))
+    }
+
+    /// Stores the blocks of a chunk in the swap directory, it only counts as swapped if that succeeded.
+    fn write_swap(&mut self, chunk_position: Vector3<i32>, chunk_data: &ChunkData) -> io::Result<()> {
+        std::fs::create_dir_all(&self.swap_directory)?;
+        std::fs::write(self.swap_path(chunk_position), chunk_data.serialize())?;
+        self.swapped.insert(chunk_position);
+        Ok(())
+    }
+
+    /// Blocks of a chunk from the swap directory, None if it was never swapped out.
+    fn read_swap(&self, chunk_position: Vector3<i32>) -> io::Result<Option<ChunkData>> {
+        if !self.swapped.contains(&chunk_position) {
+            return Ok(None);
+        }
+        let bytes = std::fs::read(self.swap_path(chunk_position))?;
+        Ok(Some(ChunkData::deserialize(&bytes)))
+    }
+}
+
+/// The swapped chunks only live as long as the terrain, saving writes them into the save file.
+impl Drop for ChunkCache {
+    fn drop(&mut self) {
+        if self.swap_directory.exists()
+            && let Err(error) = std::fs::remove_dir_all(&self.swap_directory)
+        {
+            println!("Failed to remove the swap directory {}: {}", self.swap_directory.display(), error);
+        }
+    }
+}
+
+impl Terrain {
+    /// Host memory a resident chunk takes up.
+    fn chunk_bytes(&self, chunk: &Chunk) -> usize {
+        let buffer_bytes = match chunk {
+            Chunk::Uniform(_) => 0,
+            Chunk::Data(_) => ChunkData::volume(self.chunk_size) * size_of::<u16>(),
+        };
+        buffer_bytes + size_of::<(Vector3<i32>, Chunk)>()
+    }
+
+    pub fn statistics(&self) -> TerrainStatistics {
+        TerrainStatistics {
+            resident_chunks: self.chunks.len(),
+            data_chunks: self.chunks.values().filter(|chunk| matches!(chunk, Chunk::Data(_))).count(),
+            lod_chunks: self.lod_chunks.len(),
+            resident_bytes: self.chunks.values().chain(self.lod_chunks.values()).map(|chunk| self.chunk_bytes(chunk)).sum(),
+            swapped_chunks: self.cache.swapped.len(),
+            evicted_chunks: self.cache.evicted,
+        }
+    }
+
+    /// Reads a chunk back from the swap directory if it was written there when it got evicted.
+    pub(super) fn load_swapped_chunk(
+        &self,
+        graphics: &mut Graphics,
+        chunk_position: Vector3<i32>,
+    ) -> io::Result<Option<Chunk>> {
+        let chunk_data = self.cache.read_swap(chunk_position)?;
+        Ok(chunk_data.map(|chunk_data| Chunk::from_chunk_data(graphics, chunk_data)))
+    }
+
+    /// Blocks of a chunk that is either resident or swapped out, None if it would have to be generated.
+    pub(super) fn stored_chunk_data(&self, chunk_position: Vector3<i32>) -> io::Result<Option<ChunkData>> {
+        if let Some(chunk) = self.chunks.get(&chunk_position) {
+            return Ok(Some(chunk.to_chunk_data(self.chunk_size)));
+        }
+        let mut chunk_data = self.cache.read_swap(chunk_position)?;
+        if let Some(chunk_data) = &mut chunk_data {
+            self.apply_deferred_edits(chunk_data, chunk_position);
+        }
+        Ok(chunk_data)
+    }
+
+    pub(super) fn swapped_chunk_positions(&self) -> impl Iterator<Item = &Vector3<i32>> {
+        self.cache.swapped.iter().filter(|position| !self.chunks.contains_key(position))
+    }
+
+    /// Drops the least recently used chunks outside of the loaded area until the stored chunks fit into
+    /// `GraphicsSettings::chunk_memory_budget`. Unloaded levels of detail go first since they are cheap to rebuild,
+    /// edited chunks are written to the swap directory before they are dropped.
+    pub fn evict_chunks(&mut self, graphics: &Graphics) {
+        self.cache.tick += 1;
+        let loaded = self
+            .chunks
+            .keys()
+            .copied()
+            .filter(|position| graphics.is_chunk_loaded(*position))
+            .collect::<Vec<_>>();
+        for position in loaded {
+            self.cache.touch(position);
+        }
+
+        let budget = graphics.settings.graphics_settings.chunk_memory_budget;
+        let mut resident_bytes = self.statistics().resident_bytes;
+        if resident_bytes <= budget {
+            return;
+        }
+
+        let unloaded_lod_chunks = self
+            .lod_chunks
+            .keys()
+            .copied()
+            .filter(|(level, position)| !graphics.is_lod_chunk_loaded(*level, *position))
+            .collect::<Vec<_>>();
+        for key in unloaded_lod_chunks {
+            if resident_bytes <= budget {
+                return;
+            }
+            let chunk = self.lod_chunks.remove(&key).unwrap();
+            resident_bytes -= self.chunk_bytes(&chunk);
+        }
+
+        let candidates = self
+            .chunks
+            .iter()
+            .filter(|(position, _)| !graphics.is_chunk_loaded(**position))
+            .map(|(position, chunk)| {
+                let last_used = self.cache.last_used.get(position).copied().unwrap_or(0);
+                (*position, last_used, self.chunk_bytes(chunk))
+            })
+            .collect::<Vec<_>>();
+        for position in least_recently_used(candidates, resident_bytes - budget) {
+            self.evict_chunk(position);
+        }
+    }
+
+    fn evict_chunk(&mut self, chunk_position: Vector3<i32>) {
+        if self.cache.dirty.contains(&chunk_position) {
+            let chunk_data = self.chunks[&chunk_position].to_chunk_data(self.chunk_size);
+            // dropping the chunk would lose its edits, so it stays resident until swapping it out works
+            if let Err(error) = self.cache.write_swap(chunk_position, &chunk_data) {
+                println!("Failed to swap out chunk {}, keeping it resident: {}", chunk_position, error);
+                return;
+            }
+            self.cache.dirty.remove(&chunk_position);
+        }
+        self.chunks.remove(&chunk_position);
+        self.cache.last_used.remove(&chunk_position);
+        self.cache.evicted += 1;
+    }
+}
+
+/// Picks the least recently used chunks, given as position, last use and size in bytes,
+/// until at least `excess_bytes` are freed.
+fn least_recently_used(mut candidates: Vec<(Vector3<i32>, u64, usize)>, excess_bytes: usize) -> Vec<Vector3<i32>> {
+    candidates.sort_by_key(|(_, last_used, _)| *last_used);
+    let mut freed = 0;
+    candidates
+        .into_iter()
+        .take_while(|(_, _, bytes)| {
+            let needed = freed < excess_bytes;
+            freed += bytes;
+            needed
+        })
+        .map(|(position, _, _)| position)
+        .collect()
+}
+
+impl Display for TerrainStatistics {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} resident chunks ({} with data), {} level of detail chunks, {:.1} MiB, {} swapped to disk, {} evicted",
+            self.resident_chunks,
+            self.data_chunks,
+            self.lod_chunks,
+            self.resident_bytes as f64 / (1024. * 1024.),
+            self.swapped_chunks,
+            self.evicted_chunks,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_least_recently_used() {
+        let candidates = vec![
+            (Vector3::new(0, 0, 0), 5, 100),
+            (Vector3::new(1, 0, 0), 1, 100),
+            (Vector3::new(2, 0, 0), 3, 10),
+            (Vector3::new(3, 0, 0), 2, 100),
+        ];
+        assert!(least_recently_used(candidates.clone(), 0).is_empty());
+        assert_eq!(least_recently_used(candidates.clone(), 100), vec![Vector3::new(1, 0, 0)]);
+        assert_eq!(
+            least_recently_used(candidates.clone(), 150),
+            vec![Vector3::new(1, 0, 0), Vector3::new(3, 0, 0)]
+        );
+        assert_eq!(least_recently_used(candidates, 1000).len(), 4);
+    }
+
+    #[test]
+    fn test_swap_round_trip() {
+        let mut cache = ChunkCache::new();
+        let mut chunk_data = ChunkData::filled(16, 1);
+        chunk_data.blocks_mut()[..300].fill(7);
+        let chunk_position = Vector3::new(2, -1, 5);
+        assert_eq!(cache.read_swap(chunk_position).unwrap(), None);
+        cache.write_swap(chunk_position, &chunk_data).unwrap();
+        assert_eq!(cache.read_swap(chunk_position).unwrap(), Some(chunk_data));
+
+        let swap_directory = cache.swap_directory.clone();
+        assert!(swap_directory.exists());
+        drop(cache);
+        assert!(!swap_directory.exists());
+    }
+
+    #[test]
+    fn test_evicted_dirty_chunks_are_kept() {
+        let mut terrain = Terrain::empty(16);
+        let edited = Vector3::new(0, 0, 0);
+        let generated = Vector3::new(1, 0, 0);
+        terrain.chunks.insert(edited, Chunk::Uniform(3));
+        terrain.chunks.insert(generated, Chunk::Uniform(4));
+        terrain.cache.mark_dirty(edited);
+
+        terrain.evict_chunk(edited);
+        terrain.evict_chunk(generated);
+        assert!(terrain.chunks.is_empty());
+        assert_eq!(terrain.statistics().swapped_chunks, 1);
+        assert_eq!(terrain.statistics().evicted_chunks, 2);
+        assert_eq!(terrain.stored_chunk_data(edited).unwrap(), Some(ChunkData::filled(16, 3)));
+        assert_eq!(terrain.stored_chunk_data(generated).unwrap(), None);
+        assert_eq!(terrain.swapped_chunk_positions().copied().collect::<Vec<_>>(), vec![edited]);
+    }
+}
@@ -0,0 +1,149 @@
+use crate::game_state::load_store::Serializeable;
+
+/// Block ids of a cubic chunk, indexed like `compute_1D_index` in util.glsl
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkData {
+    size: u32,
+    blocks: Box<[u16]>,
+}
+
+impl ChunkData {
+    pub fn new(size: u32, blocks: Box<[u16]>) -> Self {
+        assert_eq!(
+            blocks.len(),
+            Self::volume(size),
+            "a chunk of size {size} needs exactly {} blocks",
+            Self::volume(size)
+        );
+        Self { size, blocks }
+    }
+
+    pub fn filled(size: u32, block: u16) -> Self {
+        Self::new(size, vec![block; Self::volume(size)].into_boxed_slice())
+    }
+
+    pub fn volume(size: u32) -> usize {
+        (size as usize).pow(3)
+    }
+
+    pub fn size(&self) -> u32 {
+        self.size
+    }
+
+    pub fn blocks(&self) -> &[u16] {
+        &self.blocks
+    }
+
+    pub fn blocks_mut(&mut self) -> &mut [u16] {
+        &mut self.blocks
+    }
+
+    pub fn uniform_block(&self) -> Option<u16> {
+        uniform_block(&self.blocks)
+    }
+}
+
+/// The most common non-air block, so thin structures stay visible from afar. Ties go to the lower id.
+fn coarse_block(blocks: &[u16; 8]) -> u16 {
+    blocks
+        .iter()
+        .filter(|block| **block != 0)
+        .map(|block| (blocks.iter().filter(|other| *other == block).count(), *block))
+        .max_by(|(count_a, block_a), (count_b, block_b)| count_a.cmp(count_b).then(block_b.cmp(block_a)))
+        .map_or(0, |(_, block)| block)
+}
+
+/// Block standing for the cube of cube_size³ blocks at origin in a chunk, as the coarser levels of detail see it.
+/// Combines 2³ blocks at a time, so the cube size has to be a power of two.
+pub fn coarse_block_of_cube(blocks: &[u16], chunk_size: u32, origin: [u32; 3], cube_size: u32) -> u16 {
+    if cube_size == 1 {
+        return blocks[(origin[0] + origin[1] * chunk_size + origin[2] * chunk_size * chunk_size) as usize];
+    }
+    let half = cube_size / 2;
+    let octant_blocks = [0, 1, 2, 3, 4, 5, 6, 7].map(|octant: u32| {
+        let offset = [octant & 1, octant >> 1 & 1, octant >> 2].map(|x| x * half);
+        coarse_block_of_cube(blocks, chunk_size, std::array::from_fn(|axis| origin[axis] + offset[axis]), half)
+    });
+    coarse_block(&octant_blocks)
+}
+
+/// Returns the block id if all blocks are the same.
+pub fn uniform_block(blocks: &[u16]) -> Option<u16> {
+    let first = *blocks.first()?;
+    blocks.iter().all(|x| *x == first).then_some(first)
+}
+
+impl Serializeable for ChunkData {
+    fn serialize(&self) -> Vec<u8> {
+        let data = self.blocks.iter().flat_map(
+            |x| x.to_be_bytes()
+        ).collect();
+        data
+    }
+
+    fn deserialize(data: &[u8]) -> Self {
+        let blocks = data.chunks_exact(2).map(
+            |c|
+                u16::from_be_bytes([c[0], c[1]])
+        ).collect::<Box<[u16]>>();
+        let size = (blocks.len() as f64).cbrt().round() as u32;
+        Self::new(size, blocks)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serialize_round_trip() {
+        let blocks = (0..ChunkData::volume(16) as u16).collect::<Box<[u16]>>();
+        let chunk = ChunkData::new(16, blocks);
+        let bytes = chunk.serialize();
+        assert_eq!(bytes.len(), ChunkData::volume(16) * size_of::<u16>());
+        assert_eq!(ChunkData::deserialize(&bytes), chunk);
+    }
+
+    #[test]
+    fn test_uniform_block() {
+        assert_eq!(ChunkData::filled(16, 0).uniform_block(), Some(0));
+        assert_eq!(ChunkData::filled(16, 2).uniform_block(), Some(2));
+
+        let mut blocks = vec![2; ChunkData::volume(16)];
+        blocks[ChunkData::volume(16) - 1] = 0;
+        assert_eq!(ChunkData::new(16, blocks.into_boxed_slice()).uniform_block(), None);
+    }
+
+    #[test]
+    fn test_coarse_block() {
+        assert_eq!(coarse_block(&[0; 8]), 0);
+        assert_eq!(coarse_block(&[0, 0, 0, 0, 0, 0, 0, 3]), 3);
+        assert_eq!(coarse_block(&[1, 2, 2, 0, 0, 0, 0, 0]), 2);
+        assert_eq!(coarse_block(&[3, 1, 3, 1, 0, 0, 0, 0]), 1);
+    }
+
+    #[test]
+    fn test_coarse_block_of_cube() {
+        let mut blocks = vec![0; ChunkData::volume(16)];
+        let index = |x: usize, y: usize, z: usize| x + y * 16 + z * 16 * 16;
+        for corner in 0..8 {
+            blocks[index(corner & 1, corner >> 1 & 1, corner >> 2)] = 1;
+        }
+        blocks[index(2, 0, 0)] = 2;
+        blocks[index(8, 8, 8)] = 3;
+
+        assert_eq!(coarse_block_of_cube(&blocks, 16, [2, 0, 0], 1), 2);
+        assert_eq!(coarse_block_of_cube(&blocks, 16, [2, 0, 0], 2), 2);
+        // the full octant and the single block count once each, the tie goes to the lower id
+        assert_eq!(coarse_block_of_cube(&blocks, 16, [0, 0, 0], 4), 1);
+        assert_eq!(coarse_block_of_cube(&blocks, 16, [8, 8, 8], 8), 3);
+        assert_eq!(coarse_block_of_cube(&blocks, 16, [4, 4, 4], 4), 0);
+        assert_eq!(coarse_block_of_cube(ChunkData::filled(16, 5).blocks(), 16, [0, 0, 0], 16), 5);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_wrong_size() {
+        ChunkData::new(32, vec![0; ChunkData::volume(16)].into_boxed_slice());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::input_state::{InputState, PressState};
+use nalgebra::Vector3;
+use std::f32::consts::TAU;
+use winit::event::VirtualKeyCode;
+
+pub struct TimeOfDay {
+    /// fraction of the day, 0 is midnight, 0.25 sunrise, 0.5 noon and 0.75 sunset
+    pub time: f32,
+    /// length of a full day in seconds
+    pub day_length: f32,
+    /// stops the time and with it the sun
+    pub paused: bool,
+}
+
+impl TimeOfDay {
+    const PAUSE_KEY: VirtualKeyCode = VirtualKeyCode::T;
+    /// held to move the time backwards and forwards
+    const SCRUB_KEYS: [VirtualKeyCode; 2] = [VirtualKeyCode::Comma, VirtualKeyCode::Period];
+    /// days scrubbed per second of holding a scrub key
+    const SCRUB_SPEED: f32 = 0.1;
+    /// tilt of the sun path towards -z, so the sun is not straight above the player at noon
+    const SUN_PATH_TILT: f32 = 0.3;
+
+    pub fn new() -> Self {
+        Self {
+            time: 0.35,
+            day_length: 600.,
+            paused: false,
+        }
+    }
+
+    /// Advances the time and handles pausing and scrubbing it.
+    pub fn update(&mut self, input_state: &InputState, delta_time: f32) {
+        if input_state.is_key_pressed(Self::PAUSE_KEY, PressState::Down) {
+            self.paused = !self.paused;
+            println!("time of day {}", if self.paused { "paused" } else { "resumed" });
+        }
+        let scrub_direction = input_state.is_key_pressed(Self::SCRUB_KEYS[1], PressState::Held) as i32
+            - input_state.is_key_pressed(Self::SCRUB_KEYS[0], PressState::Held) as i32;
+        self.advance(scrub_direction as f32 * Self::SCRUB_SPEED * delta_time);
+
+        if !self.paused {
+            self.advance(delta_time / self.day_length);
+        }
+    }
+
+    pub fn advance(&mut self, days: f32) {
+        self.time = (self.time + days).rem_euclid(1.);
+    }
+
+    /// Normalized direction towards the sun, below the horizon at night.
+    pub fn sun_direction(&self) -> Vector3<f32> {
+        let angle = (self.time - 0.25) * TAU;
+        Vector3::new(angle.cos(), angle.sin(), -Self::SUN_PATH_TILT).normalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: f32) -> TimeOfDay {
+        TimeOfDay { time, ..TimeOfDay::new() }
+    }
+
+    #[test]
+    fn test_sun_direction() {
+        assert!(at(0.5).sun_direction().y > 0.9);
+        assert!(at(0.).sun_direction().y < -0.9);
+        assert!(at(0.25).sun_direction().y.abs() < 1e-5);
+        assert!(at(0.3).sun_direction().x > 0.);
+        assert!(at(0.7).sun_direction().x < 0.);
+        assert!((at(0.8).sun_direction().norm() - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn test_advance_wraps() {
+        let mut time_of_day = at(0.9);
+        time_of_day.advance(0.2);
+        assert!((time_of_day.time - 0.1).abs() < 1e-5);
+        time_of_day.advance(-0.3);
+        assert!((time_of_day.time - 0.8).abs() < 1e-5);
+    }
+}
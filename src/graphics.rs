@@ -4,9 +4,10 @@ use crate::graphics::render_core::RenderCore;
 use crate::graphics::vulkano_core::VulkanoCore;
 use crate::input_state::InputState;
 use crate::settings::Settings;
-use crate::shaders::rendering::LookingAtBlock;
+use crate::settings::graphics_settings::GraphicsSettings;
+use crate::shaders::rendering::{LookingAtBlock, PushConstants};
 
-use vulkano::command_buffer::CommandBufferUsage;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage};
 use vulkano::pipeline::Pipeline;
 use vulkano::sync::GpuFuture;
 use vulkano::sync;
@@ -16,10 +17,14 @@ use nalgebra::Vector3;
 
 mod allocators;
 mod buffers;
+mod post_processing;
 mod render_core;
+mod upscaling;
 pub mod vulkano_core;
 
 pub mod terrain_functionality;
+pub mod distance_field_validation;
+pub mod traversal_benchmark;
 
 pub struct Graphics {
     previous_frame_end: Option<Box<dyn GpuFuture>>,
@@ -27,12 +32,36 @@ pub struct Graphics {
     pub render_core: RenderCore,
     pub settings: Settings,
     cursor_confined: bool,
+    /// chunk the loaded area was last centered around
+    loaded_area_center: Vector3<i32>,
+    /// frames drawn so far, seeds the random sampling of the shaders
+    frame: u32,
+    /// frames summed up in the accumulation image, 0 restarts the accumulation
+    accumulated_frames: u32,
+    /// camera of the accumulated frames, moving it restarts the accumulation
+    accumulated_cam_transform: [[f32; 4]; 4],
+    /// sun of the accumulated frames, the time of day moving it restarts the accumulation
+    accumulated_sun_direction: Vector3<f32>,
+    /// selects the jitter offset and the history image of the temporal upscaling
+    upscaled_frames: u32,
+    /// whether the history image holds the previous frame
+    upscaling_history_valid: bool,
+    /// camera of the previous frame, the temporal upscaling reprojects the history with it
+    previous_cam_transform: [[f32; 4]; 4],
+    /// block under the crosshair in the last frame the gpu finished, see `poll_looking_at`
+    looking_at: Option<LookingAtBlock>,
 }
 impl Graphics {
-    pub const CHUNK_SIZE: u32 = 64;
-    pub const CHUNK_VOLUME: u32 =
-        Graphics::CHUNK_SIZE * Graphics::CHUNK_SIZE * Graphics::CHUNK_SIZE;
+    /// edge length of the bricks whose occupancy lets rays skip empty space, see `BRICK_SIZE` in util.glsl
+    pub const BRICK_SIZE: u32 = 8;
+    /// blocks of the neighbouring chunks taken into account by a distance field, see `DISTANCE_FIELD_PADDING` in util.glsl
+    pub const DISTANCE_FIELD_PADDING: u32 = 16;
     pub fn new(settings: Settings) -> (Self, EventLoop<()>) {
+        let chunk_size = settings.graphics_settings.chunk_size;
+        assert!(
+            chunk_size >= 16 && chunk_size.is_power_of_two(),
+            "chunk size must be a power of two of at least 16, got {chunk_size}"
+        );
         let (vulkano_core, event_loop) = VulkanoCore::new();
 
         let previous_frame_end: Option<Box<dyn GpuFuture>> =
@@ -47,6 +76,15 @@ impl Graphics {
                 render_core,
                 settings,
                 cursor_confined: false,
+                loaded_area_center: Vector3::zeros(),
+                frame: 0,
+                accumulated_frames: 0,
+                accumulated_cam_transform: [[0.; 4]; 4],
+                accumulated_sun_direction: Vector3::zeros(),
+                upscaled_frames: 0,
+                upscaling_history_valid: false,
+                previous_cam_transform: [[0.; 4]; 4],
+                looking_at: None,
             },
             event_loop,
         )
@@ -80,7 +118,9 @@ impl Graphics {
                 DeviceEvent::MouseMotion { delta } => {
                     input_state.update_mouse(delta);
                 }
-                DeviceEvent::MouseWheel { .. } => {}
+                DeviceEvent::MouseWheel { delta } => {
+                    input_state.update_scroll(delta);
+                }
                 DeviceEvent::Key(input) => {
                     input_state.update_keys(input);
                 }
@@ -89,6 +129,7 @@ impl Graphics {
             Event::RedrawEventsCleared => {
                 let old_chunk_pos = game_state.get_player_chunk();
                 update(&mut game_state, &input_state, &mut self, control_flow);
+                game_state.terrain.apply_block_edits(&mut self);
                 self.update_chunks(&mut game_state, Some(old_chunk_pos));
 
                 self.draw_frame(&game_state);
@@ -109,6 +150,7 @@ impl Graphics {
         }
 
         self.recreate_swapchain_if_needed();
+        let push_constants = self.frame_push_constants(game_state);
 
         let (image_index, suboptimal, acquire_future) =
             match vulkano::swapchain::acquire_next_image(
@@ -129,7 +171,8 @@ impl Graphics {
             self.recreate_swapchain();
         }
 
-        let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
+        let [render_width, render_height, _] = self.render_core.buffers.hdr_image.extent();
+        let mut builder = AutoCommandBufferBuilder::primary(
             &self.vulkano_core.allocators.commmand_buffer,
             self.vulkano_core.queue.queue_family_index(),
             CommandBufferUsage::OneTimeSubmit,
@@ -153,7 +196,7 @@ impl Graphics {
                     .layout()
                     .clone(),
                 0,
-                game_state.get_push_constants(),
+                push_constants,
             )
             .unwrap()
             .bind_descriptor_sets(
@@ -165,12 +208,13 @@ impl Graphics {
                     .layout()
                     .clone(),
                 0,
-                self.render_core.pipelines.raytrace_pipeline.descriptor_sets[image_index as usize]
-                    .clone(),
+                self.render_core.pipelines.raytrace_pipeline.descriptor_set.clone(),
             )
             .unwrap()
-            .dispatch([image_extent[0] / 16 + 1, image_extent[1] / 16 + 1, 1])
+            .dispatch([render_width / 16 + 1, render_height / 16 + 1, 1])
             .unwrap();
+        self.record_upscaling(&mut builder, image_extent, &push_constants);
+        self.record_post_processing(&mut builder, image_index as usize, image_extent);
 
         let command_buffer = builder.build().unwrap();
 
@@ -209,6 +253,60 @@ impl Graphics {
             return;
         }
         SwapchainResources::recreate_swapchain(self);
+        self.reset_accumulation();
+        self.upscaling_history_valid = false;
+    }
+
+    /// Adds the block under the crosshair of the last frame and counts the frames accumulated with the current camera,
+    /// restarting when it moved.
+    fn frame_push_constants(&mut self, game_state: &GameState) -> PushConstants {
+        let mut push_constants = game_state.get_push_constants();
+        if let Some(sun_direction) = self.settings.graphics_settings.sun_direction {
+            push_constants.sun_direction = sun_direction.normalize();
+        }
+        push_constants.jitter = self.upscaling_jitter().into();
+        push_constants.frame = self.frame;
+        self.frame = self.frame.wrapping_add(1);
+        self.poll_looking_at();
+        if let Some(looking_at) = &self.looking_at {
+            let [x, y, z] = looking_at.target_block().into();
+            push_constants.target_block = [x, y, z, 1].into();
+            let [x, y, z] = looking_at.placement_block().into();
+            push_constants.placement_block = [x, y, z, 1].into();
+        }
+        if !self.settings.graphics_settings.accumulation {
+            return push_constants;
+        }
+        let cam_transform: [[f32; 4]; 4] = push_constants.cam_transform.into();
+        if cam_transform != self.accumulated_cam_transform || push_constants.sun_direction != self.accumulated_sun_direction {
+            self.accumulated_cam_transform = cam_transform;
+            self.accumulated_sun_direction = push_constants.sun_direction;
+            self.reset_accumulation();
+        }
+        push_constants.accumulated_frames = self.accumulated_frames;
+        self.accumulated_frames = self.accumulated_frames.saturating_add(1);
+        push_constants
+    }
+
+    /// Discards the accumulated frames, has to be called whenever the rendered image changes.
+    pub fn reset_accumulation(&mut self) {
+        self.accumulated_frames = 0;
+    }
+
+    pub fn toggle_placement_preview(&mut self) {
+        self.wait_and_reset_last_frame_end();
+        let graphics_settings = &mut self.settings.graphics_settings;
+        graphics_settings.placement_preview = !graphics_settings.placement_preview;
+        self.render_core.buffers.gpu_graphics_settings_buffer.write().unwrap().placement_preview = graphics_settings.placement_preview as u32;
+        self.reset_accumulation();
+    }
+
+    pub fn toggle_accumulation(&mut self) {
+        self.wait_and_reset_last_frame_end();
+        let graphics_settings = &mut self.settings.graphics_settings;
+        graphics_settings.accumulation = !graphics_settings.accumulation;
+        self.render_core.buffers.gpu_graphics_settings_buffer.write().unwrap().accumulation = graphics_settings.accumulation as u32;
+        self.reset_accumulation();
     }
 
     pub fn wait_and_reset_last_frame_end(&mut self) {
@@ -224,6 +322,35 @@ impl Graphics {
 
         self.previous_frame_end = Some(sync::now(self.vulkano_core.device.clone()).boxed());
     }
+    /// Changes the render distance, reallocating the chunk images and re-uploading the chunks around the player.
+    /// Chunks that are already stored in the terrain are not regenerated.
+    pub fn set_render_distance(
+        &mut self,
+        game_state: &mut GameState,
+        render_distance: u8,
+        vertical_render_distance: u8,
+    ) {
+        let render_distance = render_distance.clamp(1, GraphicsSettings::MAX_RENDER_DISTANCE);
+        let vertical_render_distance =
+            vertical_render_distance.clamp(1, GraphicsSettings::MAX_RENDER_DISTANCE);
+        let graphics_settings = &mut self.settings.graphics_settings;
+        if render_distance == graphics_settings.render_distance
+            && vertical_render_distance == graphics_settings.vertical_render_distance
+        {
+            return;
+        }
+        graphics_settings.render_distance = render_distance;
+        graphics_settings.vertical_render_distance = vertical_render_distance;
+        self.wait_and_reset_last_frame_end();
+        self.render_core
+            .recreate_chunk_resources(&self.vulkano_core, &self.settings);
+        self.update_chunks(game_state, None);
+    }
+
+    pub fn chunk_size(&self) -> u32 {
+        self.settings.graphics_settings.chunk_size
+    }
+
     pub fn toggle_confine(&mut self) {
         self.cursor_confined = !self.cursor_confined;
         self.vulkano_core
@@ -243,41 +370,52 @@ impl Graphics {
         self.wait_and_reset_last_frame_end();
         let buffer = self.render_core.buffers.player_raycast_buffer.clone();
         let looking_at_guard = buffer.read().unwrap();
+        looking_at_guard.hit()
+    }
 
-        if looking_at_guard.block_id > 0 {
-            return Some(LookingAtBlock {
-                hit_point: looking_at_guard.hit_point,
-                block_id: looking_at_guard.block_id,
-                hit_normal: looking_at_guard.hit_normal,
-            });
+    /// Takes the block under the crosshair from the last frame if the gpu finished it, without waiting for it.
+    /// The buffer can't be read while a frame writing it is in flight, the previous result is kept until then.
+    fn poll_looking_at(&mut self) {
+        if let Some(future) = self.previous_frame_end.as_mut() {
+            future.cleanup_finished();
+        }
+        if let Ok(looking_at_guard) = self.render_core.buffers.player_raycast_buffer.read() {
+            self.looking_at = looking_at_guard.hit();
         }
-        None
     }
     
     pub fn add_pov(&mut self, difference: f32) {
         self.wait_and_reset_last_frame_end();
         self.settings.graphics_settings.field_of_view = (self.settings.graphics_settings.field_of_view + difference).clamp(50., 160.);
         self.render_core.buffers.gpu_graphics_settings_buffer.write().unwrap().fov = self.settings.graphics_settings.field_of_view.to_radians();
+        self.reset_accumulation();
     }
     pub fn set_pov(&mut self, value: f32) {
         self.wait_and_reset_last_frame_end();
         self.settings.graphics_settings.field_of_view = value.clamp(50., 160.);
         self.render_core.buffers.gpu_graphics_settings_buffer.write().unwrap().fov = self.settings.graphics_settings.field_of_view.to_radians();
+        self.reset_accumulation();
     }
 }
 
 pub fn chunk_buffer_index(chunk_position: Vector3<i32>, settings: &Settings) -> usize {
-    let render_sl = 2 * settings.graphics_settings.render_distance as i32 + 1;
+    let area_size = settings.graphics_settings.loaded_area_size();
     chunk_position
-        .map(|x| x.rem_euclid(render_sl))
-        .dot(&Vector3::new(1, render_sl, render_sl * render_sl)) as usize
+        .zip_map(&area_size, |x, size| x.rem_euclid(size))
+        .dot(&Vector3::new(1, area_size.x, area_size.x * area_size.y)) as usize
+}
+
+/// Index of a chunk of a coarser level of detail in `lod_block_data_buffers`, the levels are stored one after another.
+pub fn lod_chunk_buffer_index(level: u32, lod_chunk_position: Vector3<i32>, settings: &Settings) -> usize {
+    (level as usize - 1) * settings.graphics_settings.loaded_chunk_count()
+        + chunk_buffer_index(lod_chunk_position, settings)
 }
 
-pub fn block_in_chunk_index(block_position: Vector3<i32>) -> usize {
-    let pos = block_position.map(|x| x.rem_euclid(Graphics::CHUNK_SIZE as i32) as u32);
+pub fn block_in_chunk_index(block_position: Vector3<i32>, chunk_size: u32) -> usize {
+    let pos = block_position.map(|x| x.rem_euclid(chunk_size as i32) as u32);
     pos.dot(&Vector3::new(
         1,
-        Graphics::CHUNK_SIZE,
-        Graphics::CHUNK_SIZE * Graphics::CHUNK_SIZE,
+        chunk_size,
+        chunk_size * chunk_size,
     )) as usize
 }
\ No newline at end of file
@@ -1,16 +1,29 @@
+mod auto_exposure;
+mod block_material;
+mod chunk_info;
+pub mod chunk_lights;
 mod gpu_graphics_settings;
 
 mod looking_at;
 mod textures;
+mod traversal_stats;
 
+use crate::game_state::terrain::{Chunk, ChunkBuffer};
+use crate::game_state::terrain::chunk_data::ChunkData;
 use crate::graphics::Graphics;
+use crate::graphics::render_core::pipelines::terrain_distance_pipeline;
 use crate::graphics::vulkano_core::VulkanoCore;
 use crate::settings::graphics_settings::GraphicsSettings;
+use crate::game_state::terrain::block::Block;
+use crate::shaders::auto_exposure::AutoExposure;
+use crate::shaders::rendering::BlockMaterial;
 use crate::shaders::rendering::LookingAtBlock;
 use crate::shaders::rendering::GpuGraphicsSettings;
+use crate::shaders::rendering::TraversalStats;
 use std::sync::Arc;
 use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
 use vulkano::format::Format;
+use vulkano::DeviceSize;
 use vulkano::image::view::ImageView;
 use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
 use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
@@ -21,8 +34,30 @@ pub struct Buffers {
     pub textures: Textures,
     pub block_data_buffers: Box<[Arc<Image>]>,
     pub distance_data_buffers: Box<[Arc<Image>]>,
+    /// one voxel per brick of `Graphics::BRICK_SIZE`³ blocks, non-zero if the brick contains any block
+    pub brick_occupancy_buffers: Box<[Arc<Image>]>,
+    /// scratch image distance fields are computed in before being stored into the chunks
+    pub distance_region_buffer: Arc<Image>,
+    /// one entry per chunk image, see `chunk_info::chunk_info`
+    pub chunk_info_buffer: Subbuffer<[u32]>,
+    /// chunk images of the coarser levels of detail, see `lod_chunk_buffer_index`
+    pub lod_block_data_buffers: Box<[Arc<Image>]>,
+    pub lod_chunk_info_buffer: Subbuffer<[u32]>,
+    /// emissive blocks of every chunk image, `chunk_lights::LIGHT_LIST_STRIDE` entries per chunk
+    pub chunk_lights_buffer: Subbuffer<[u32]>,
+    /// light and transmission properties of every block id
+    pub block_materials_buffer: Subbuffer<[BlockMaterial]>,
     pub player_raycast_buffer: Subbuffer<LookingAtBlock>,
     pub gpu_graphics_settings_buffer: Subbuffer<GpuGraphicsSettings>,
+    pub traversal_stats_buffer: Subbuffer<TraversalStats>,
+    /// sum of the frames traced while accumulating and their count in the alpha channel, sized like the render resolution
+    pub accumulation_image: Arc<Image>,
+    /// linear colours written by the raytracer before upscaling and tonemapping and the distance to the first hit
+    /// in the alpha channel, sized like the render resolution
+    pub hdr_image: Arc<Image>,
+    pub auto_exposure_buffer: Subbuffer<AutoExposure>,
+    /// the terrain generator writes every chunk here, only chunks that aren't uniform get a host buffer of their own
+    pub chunk_generation_buffer: ChunkBuffer,
 }
 
 impl Buffers {
@@ -41,46 +76,134 @@ impl Buffers {
             MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_RANDOM_ACCESS
         );
         
-        let block_data_buffers = create_block_data_buffers(vulkano_core, &graphics_settings);
+        let auto_exposure_buffer = buffer_from_data(
+            vulkano_core,
+            AutoExposure::new(),
+            BufferUsage::STORAGE_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE
+        );
+
+        let traversal_stats_buffer = buffer_from_data(
+            vulkano_core,
+            TraversalStats::new(),
+            BufferUsage::STORAGE_BUFFER,
+            MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_RANDOM_ACCESS
+        );
+
+        let block_data_buffers = create_block_data_buffers(vulkano_core, &graphics_settings, graphics_settings.loaded_chunk_count());
         let distance_data_buffers = create_distance_data_buffers(vulkano_core, &graphics_settings);
+        let brick_occupancy_buffers = create_brick_occupancy_buffers(vulkano_core, &graphics_settings);
+        let chunk_info_buffer = create_chunk_info_buffer(vulkano_core, graphics_settings.loaded_chunk_count());
+        let lod_block_data_buffers = create_block_data_buffers(vulkano_core, &graphics_settings, graphics_settings.lod_chunk_count());
+        let lod_chunk_info_buffer = create_chunk_info_buffer(vulkano_core, graphics_settings.lod_chunk_count());
+        let distance_region_buffer = create_distance_region_buffer(vulkano_core, &graphics_settings);
+        let chunk_lights_buffer = create_chunk_info_buffer(vulkano_core, graphics_settings.loaded_chunk_count() * chunk_lights::LIGHT_LIST_STRIDE);
+        let block_materials_buffer = Buffer::from_iter(
+            vulkano_core.allocators.memory.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            (0..Block::ID_COUNT).map(|id| BlockMaterial::new(Block::from_u16(id))),
+        )
+            .unwrap();
 
         Self {
-            textures: Textures::new(vulkano_core),
+            textures: Textures::new(vulkano_core, graphics_settings),
             distance_data_buffers,
             block_data_buffers,
+            brick_occupancy_buffers,
+            distance_region_buffer,
+            chunk_info_buffer,
+            lod_block_data_buffers,
+            lod_chunk_info_buffer,
+            chunk_lights_buffer,
+            block_materials_buffer,
             player_raycast_buffer,
-            gpu_graphics_settings_buffer
+            gpu_graphics_settings_buffer,
+            traversal_stats_buffer,
+            accumulation_image: create_render_sized_image(vulkano_core, graphics_settings, Format::R32G32B32A32_SFLOAT),
+            hdr_image: create_render_sized_image(vulkano_core, graphics_settings, Format::R16G16B16A16_SFLOAT),
+            auto_exposure_buffer,
+            chunk_generation_buffer: create_chunk_generation_buffer(vulkano_core, graphics_settings),
         }
     }
 
+    /// Resizes the images the rays are traced into to the window and render scale, their content is lost.
+    pub fn recreate_render_sized_images(&mut self, vulkano_core: &VulkanoCore, graphics_settings: &GraphicsSettings) {
+        self.accumulation_image = create_render_sized_image(vulkano_core, graphics_settings, Format::R32G32B32A32_SFLOAT);
+        self.hdr_image = create_render_sized_image(vulkano_core, graphics_settings, Format::R16G16B16A16_SFLOAT);
+    }
+
+    /// Reallocates the chunk images for a changed render distance, their content is lost.
+    pub fn recreate_chunk_images(&mut self, vulkano_core: &VulkanoCore, graphics_settings: &GraphicsSettings) {
+        self.block_data_buffers = create_block_data_buffers(vulkano_core, graphics_settings, graphics_settings.loaded_chunk_count());
+        self.distance_data_buffers = create_distance_data_buffers(vulkano_core, graphics_settings);
+        self.brick_occupancy_buffers = create_brick_occupancy_buffers(vulkano_core, graphics_settings);
+        self.chunk_info_buffer = create_chunk_info_buffer(vulkano_core, graphics_settings.loaded_chunk_count());
+        self.lod_block_data_buffers = create_block_data_buffers(vulkano_core, graphics_settings, graphics_settings.lod_chunk_count());
+        self.lod_chunk_info_buffer = create_chunk_info_buffer(vulkano_core, graphics_settings.lod_chunk_count());
+        self.chunk_lights_buffer = create_chunk_info_buffer(vulkano_core, graphics_settings.loaded_chunk_count() * chunk_lights::LIGHT_LIST_STRIDE);
+    }
+
+    pub fn set_chunk_info(&self, chunk_index: usize, chunk: &Chunk) {
+        self.chunk_info_buffer.write().unwrap()[chunk_index] = chunk_info::chunk_info(chunk);
+    }
+
+    pub fn set_chunk_lights(&self, chunk_index: usize, chunk: &Chunk, chunk_size: u32) {
+        let stride = chunk_lights::LIGHT_LIST_STRIDE;
+        self.chunk_lights_buffer.write().unwrap()[chunk_index * stride..(chunk_index + 1) * stride]
+            .copy_from_slice(&chunk_lights::chunk_lights(chunk, chunk_size));
+    }
+
+    pub fn set_lod_chunk_info(&self, lod_chunk_index: usize, chunk: &Chunk) {
+        self.lod_chunk_info_buffer.write().unwrap()[lod_chunk_index] = chunk_info::chunk_info(chunk);
+    }
+
     pub fn get_chunk_image_views(&self) -> Vec<Arc<ImageView>> {
         self.block_data_buffers
             .iter()
             .map(|x| ImageView::new_default(x.clone()).unwrap())
             .collect::<Vec<_>>()
     }
+    pub fn get_lod_chunk_image_views(&self) -> Vec<Arc<ImageView>> {
+        self.lod_block_data_buffers
+            .iter()
+            .map(|x| ImageView::new_default(x.clone()).unwrap())
+            .collect::<Vec<_>>()
+    }
     pub fn get_distance_image_views(&self) -> Vec<Arc<ImageView>> {
         self.distance_data_buffers
             .iter()
             .map(|x| ImageView::new_default(x.clone()).unwrap())
             .collect::<Vec<_>>()
     }
+    pub fn get_brick_occupancy_image_views(&self) -> Vec<Arc<ImageView>> {
+        self.brick_occupancy_buffers
+            .iter()
+            .map(|x| ImageView::new_default(x.clone()).unwrap())
+            .collect::<Vec<_>>()
+    }
 }
 
 fn create_block_data_buffers(
     vulkano_core: &VulkanoCore,
     graphics_settings: &GraphicsSettings,
+    count: usize,
 ) -> Box<[Arc<Image>]> {
     let image_create_info = ImageCreateInfo {
         image_type: vulkano::image::ImageType::Dim3d,
         format: Format::R16_UINT,
-        extent: [Graphics::CHUNK_SIZE; 3],
+        extent: [graphics_settings.chunk_size; 3],
         usage: ImageUsage::STORAGE | ImageUsage::TRANSFER_DST | ImageUsage::TRANSFER_SRC,
         ..Default::default()
     };
 
-    let loaded_chunks_size = graphics_settings.render_distance as usize * 2 + 1;
-    vec![(); loaded_chunks_size * loaded_chunks_size * loaded_chunks_size]
+    vec![(); count]
         .iter()
         .map(|_| {
             Image::new(
@@ -101,13 +224,38 @@ fn create_distance_data_buffers(
     let image_create_info = ImageCreateInfo {
         image_type: vulkano::image::ImageType::Dim3d,
         format: Format::R8_UINT,
-        extent: [Graphics::CHUNK_SIZE; 3],
+        extent: [graphics_settings.chunk_size; 3],
+        usage: ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC,
+        ..Default::default()
+    };
+
+    vec![(); graphics_settings.loaded_chunk_count()]
+        .iter()
+        .map(|_| {
+            Image::new(
+                vulkano_core.allocators.memory.clone(),
+                image_create_info.clone(),
+                AllocationCreateInfo::default(),
+            )
+                .unwrap()
+        })
+        .collect::<Vec<_>>()
+        .into_boxed_slice()
+}
+
+fn create_brick_occupancy_buffers(
+    vulkano_core: &VulkanoCore,
+    graphics_settings: &GraphicsSettings,
+) -> Box<[Arc<Image>]> {
+    let image_create_info = ImageCreateInfo {
+        image_type: vulkano::image::ImageType::Dim3d,
+        format: Format::R8_UINT,
+        extent: [graphics_settings.chunk_size / Graphics::BRICK_SIZE; 3],
         usage: ImageUsage::STORAGE,
         ..Default::default()
     };
 
-    let loaded_chunks_size = graphics_settings.render_distance as usize * 2 + 1;
-    vec![(); loaded_chunks_size * loaded_chunks_size * loaded_chunks_size]
+    vec![(); graphics_settings.loaded_chunk_count()]
         .iter()
         .map(|_| {
             Image::new(
@@ -121,6 +269,79 @@ fn create_distance_data_buffers(
         .into_boxed_slice()
 }
 
+fn create_distance_region_buffer(
+    vulkano_core: &VulkanoCore,
+    graphics_settings: &GraphicsSettings,
+) -> Arc<Image> {
+    Image::new(
+        vulkano_core.allocators.memory.clone(),
+        ImageCreateInfo {
+            image_type: vulkano::image::ImageType::Dim3d,
+            format: Format::R8_UINT,
+            extent: [terrain_distance_pipeline::scratch_region_size(graphics_settings.chunk_size); 3],
+            usage: ImageUsage::STORAGE,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
+    )
+        .unwrap()
+}
+
+fn create_render_sized_image(
+    vulkano_core: &VulkanoCore,
+    graphics_settings: &GraphicsSettings,
+    format: Format,
+) -> Arc<Image> {
+    let [width, height] = graphics_settings.render_extent(vulkano_core.window.inner_size().into());
+    Image::new(
+        vulkano_core.allocators.memory.clone(),
+        ImageCreateInfo {
+            image_type: vulkano::image::ImageType::Dim2d,
+            format,
+            extent: [width, height, 1],
+            usage: ImageUsage::STORAGE,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
+    )
+        .unwrap()
+}
+
+fn create_chunk_info_buffer(
+    vulkano_core: &VulkanoCore,
+    count: usize,
+) -> Subbuffer<[u32]> {
+    Buffer::from_iter(
+        vulkano_core.allocators.memory.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+            ..Default::default()
+        },
+        vec![0; count],
+    )
+        .unwrap()
+}
+
+fn create_chunk_generation_buffer(vulkano_core: &VulkanoCore, graphics_settings: &GraphicsSettings) -> ChunkBuffer {
+    Buffer::new_slice(
+        vulkano_core.allocators.memory.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::STORAGE_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+            ..Default::default()
+        },
+        ChunkData::volume(graphics_settings.chunk_size) as DeviceSize,
+    )
+        .unwrap()
+}
+
 fn buffer_from_data<T>(
     vulkano_core: &VulkanoCore,
     content: T,
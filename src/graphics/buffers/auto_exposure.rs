@@ -0,0 +1,9 @@
+use crate::shaders::auto_exposure::AutoExposure;
+
+impl AutoExposure {
+    pub fn new() -> Self {
+        Self{
+            average_luminance: 0.18,
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use crate::game_state::terrain::block::Block;
+use crate::shaders::rendering::BlockMaterial;
+
+impl BlockMaterial {
+    pub fn new(block: Block) -> Self {
+        let emission = block.emission().map_or([0.; 4], |emission| {
+            [emission.color.x, emission.color.y, emission.color.z, emission.intensity]
+        });
+        let transmission = match block {
+            Block::TransparentBlock(block) => {
+                let absorption = block.absorption();
+                [absorption.x, absorption.y, absorption.z, block.refractive_index()]
+            }
+            _ => [0., 0., 0., 1.],
+        };
+        Self {
+            emission: emission.into(),
+            transmission: transmission.into(),
+        }
+    }
+}
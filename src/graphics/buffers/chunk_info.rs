@@ -0,0 +1,13 @@
+use crate::game_state::terrain::Chunk;
+
+/// Set in the chunk info of chunks which consist of a single block type, see `chunk_info` in raytrace.glsl
+pub const UNIFORM_CHUNK_FLAG: u32 = 1 << 16;
+
+/// Encodes the per chunk metadata read by the raytracer.
+/// Uniform chunks store their block id in the lower 16 bits, all other chunks are 0.
+pub fn chunk_info(chunk: &Chunk) -> u32 {
+    match chunk {
+        Chunk::Uniform(block) => UNIFORM_CHUNK_FLAG | *block as u32,
+        Chunk::Data(_) => 0,
+    }
+}
@@ -0,0 +1,88 @@
+use crate::game_state::terrain::Chunk;
+use crate::game_state::terrain::block::Block;
+
+/// Entries per chunk in the light list buffer, the light count followed by the packed light positions,
+/// see `LIGHT_LIST_STRIDE` in raytrace.glsl
+pub const LIGHT_LIST_STRIDE: usize = 64;
+pub const MAX_LIGHTS_PER_CHUNK: usize = LIGHT_LIST_STRIDE - 1;
+
+/// Encodes the emissive blocks of a chunk for the raytracer, the in-chunk positions are packed
+/// into 10 bits per axis. Chunks with more lights keep an evenly spread subset of them.
+/// Only the border of a uniform emissive chunk can be seen, so its lights are spread over the border.
+pub fn chunk_lights(chunk: &Chunk, chunk_size: u32) -> [u32; LIGHT_LIST_STRIDE] {
+    match chunk {
+        Chunk::Uniform(block) if *block >= Block::FIRST_EMISSIVE_ID => light_list(border_blocks(chunk_size), chunk_size),
+        Chunk::Uniform(_) => [0; LIGHT_LIST_STRIDE],
+        Chunk::Data(buffer) => light_list(emissive_blocks(&buffer.read().unwrap()), chunk_size),
+    }
+}
+
+/// Indices of the emissive blocks.
+fn emissive_blocks(blocks: &[u16]) -> Vec<u32> {
+    blocks
+        .iter()
+        .enumerate()
+        .filter(|(_, block)| **block >= Block::FIRST_EMISSIVE_ID)
+        .map(|(index, _)| index as u32)
+        .collect()
+}
+
+/// Indices of the blocks on the faces of a chunk.
+fn border_blocks(chunk_size: u32) -> Vec<u32> {
+    (0..chunk_size.pow(3))
+        .filter(|index| {
+            [index % chunk_size, index / chunk_size % chunk_size, index / (chunk_size * chunk_size)]
+                .iter()
+                .any(|x| *x == 0 || *x == chunk_size - 1)
+        })
+        .collect()
+}
+
+fn light_list(lights: Vec<u32>, chunk_size: u32) -> [u32; LIGHT_LIST_STRIDE] {
+    assert!(chunk_size <= 1 << 10, "light positions are packed into 10 bits per axis");
+    let step = lights.len().div_ceil(MAX_LIGHTS_PER_CHUNK).max(1);
+
+    let mut list = [0; LIGHT_LIST_STRIDE];
+    let mut count = 0;
+    for index in lights.into_iter().step_by(step) {
+        let (x, y, z) = (index % chunk_size, index / chunk_size % chunk_size, index / (chunk_size * chunk_size));
+        count += 1;
+        list[count] = x | y << 10 | z << 20;
+    }
+    list[0] = count as u32;
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_light_list() {
+        let mut blocks = vec![0; 16 * 16 * 16];
+        blocks[1 + 2 * 16 + 3 * 256] = Block::FIRST_EMISSIVE_ID;
+        let list = light_list(emissive_blocks(&blocks), 16);
+        assert_eq!(list[0], 1);
+        assert_eq!(list[1], 1 | 2 << 10 | 3 << 20);
+
+        let many_lights = vec![Block::FIRST_EMISSIVE_ID; 16 * 16 * 16];
+        let list = light_list(emissive_blocks(&many_lights), 16);
+        assert!(list[0] as usize <= MAX_LIGHTS_PER_CHUNK);
+        assert!(list[0] as usize > MAX_LIGHTS_PER_CHUNK / 2);
+        assert!(list[1..=list[0] as usize].windows(2).all(|pair| pair[0] != pair[1]));
+    }
+
+    #[test]
+    fn test_uniform_chunk_lights() {
+        assert_eq!(chunk_lights(&Chunk::Uniform(0), 16)[0], 0);
+
+        let list = chunk_lights(&Chunk::Uniform(Block::FIRST_EMISSIVE_ID), 16);
+        assert!(list[0] as usize <= MAX_LIGHTS_PER_CHUNK);
+        assert!(list[0] as usize > MAX_LIGHTS_PER_CHUNK / 2);
+        for light in &list[1..=list[0] as usize] {
+            let position = [light & 1023, light >> 10 & 1023, light >> 20];
+            assert!(position.iter().any(|x| *x == 0 || *x == 15));
+        }
+        assert_eq!(border_blocks(16).len(), 16 * 16 * 16 - 14 * 14 * 14);
+    }
+}
@@ -4,7 +4,22 @@ use crate::shaders::rendering::GpuGraphicsSettings;
 impl GpuGraphicsSettings {
     pub fn new(graphics_settings: &GraphicsSettings) -> Self{
         Self{
-            fov: graphics_settings.field_of_view.to_radians()
+            fov: graphics_settings.field_of_view.to_radians(),
+            normal_mapping: graphics_settings.normal_mapping as u32,
+            parallax_depth: graphics_settings.parallax_depth,
+            brick_skipping: graphics_settings.brick_skipping as u32,
+            shadows: graphics_settings.shadows as u32,
+            sun_angular_radius: graphics_settings.sun_angular_radius.to_radians(),
+            ambient_occlusion: graphics_settings.ambient_occlusion as u32,
+            accumulation: graphics_settings.accumulation as u32,
+            path_bounces: graphics_settings.path_bounces,
+            block_outline: graphics_settings.block_outline as u32,
+            placement_preview: graphics_settings.placement_preview as u32,
+            fog_density: graphics_settings.fog_density,
+            fog_height_falloff: graphics_settings.fog_height_falloff,
+            fog_color: graphics_settings.fog_color,
+            underwater_fog_density: graphics_settings.underwater_fog_density,
+            underwater_fog_color: graphics_settings.underwater_fog_color,
         }
     }
 }
\ No newline at end of file
@@ -1,4 +1,5 @@
 use crate::shaders::rendering::LookingAtBlock;
+use nalgebra::Vector3;
 
 impl LookingAtBlock {
     pub fn new() -> Self {
@@ -8,4 +9,23 @@ impl LookingAtBlock {
             hit_normal: Default::default(),
         }
     }
-}
\ No newline at end of file
+
+    /// Copy of the result, None if the ray hit nothing
+    pub fn hit(&self) -> Option<Self> {
+        (self.block_id > 0).then(|| Self {
+            hit_point: self.hit_point,
+            block_id: self.block_id,
+            hit_normal: self.hit_normal,
+        })
+    }
+
+    /// Position of the block under the crosshair
+    pub fn target_block(&self) -> Vector3<i32> {
+        (self.hit_point - self.hit_normal * 0.2).map(|x| x.floor() as i32)
+    }
+
+    /// Position a newly placed block goes to, in front of the hit face
+    pub fn placement_block(&self) -> Vector3<i32> {
+        (self.hit_point + self.hit_normal * 0.2).map(|x| x.floor() as i32)
+    }
+}
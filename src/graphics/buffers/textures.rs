@@ -1,25 +1,42 @@
 use std::sync::Arc;
 use vulkano::device::DeviceOwned;
-use vulkano::image::sampler::{Sampler, SamplerCreateInfo};
+use vulkano::image::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
 use vulkano::image::view::ImageView;
 use crate::graphics::vulkano_core::VulkanoCore;
-use crate::textures::create_block_texture_view;
+use crate::settings::graphics_settings::GraphicsSettings;
+use crate::textures::{create_block_normal_view, create_block_texture_view, create_color_grading_lut_view};
 
 pub struct Textures {
     pub sampler: Arc<Sampler>,
     pub image_view: Arc<ImageView>,
+    pub normal_view: Arc<ImageView>,
+    pub color_grading_lut: Arc<ImageView>,
+    /// interpolates between the entries of the colour grading table
+    pub lut_sampler: Arc<Sampler>,
 }
 
 impl Textures {
-    pub fn new(vulkano_core: &VulkanoCore) -> Self {
+    pub fn new(vulkano_core: &VulkanoCore, graphics_settings: &GraphicsSettings) -> Self {
         let textures = create_block_texture_view(vulkano_core);
         let sampler = Sampler::new(
             vulkano_core.device.clone(),
             SamplerCreateInfo::default(),
         ).unwrap();
+        let lut_sampler = Sampler::new(
+            vulkano_core.device.clone(),
+            SamplerCreateInfo {
+                mag_filter: Filter::Linear,
+                min_filter: Filter::Linear,
+                address_mode: [SamplerAddressMode::ClampToEdge; 3],
+                ..Default::default()
+            },
+        ).unwrap();
         Self{
             image_view: textures,
-            sampler
+            normal_view: create_block_normal_view(vulkano_core),
+            color_grading_lut: create_color_grading_lut_view(vulkano_core, &graphics_settings.color_grading),
+            sampler,
+            lut_sampler,
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,11 @@
+use crate::shaders::rendering::TraversalStats;
+
+impl TraversalStats {
+    pub fn new() -> Self {
+        Self{
+            enabled: 0,
+            rays: 0,
+            steps: 0,
+        }
+    }
+}
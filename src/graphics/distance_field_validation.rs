@@ -0,0 +1,341 @@
+use std::collections::HashMap;
+use nalgebra::Vector3;
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::command_buffer::{CommandBufferUsage, CopyImageToBufferInfo};
+use vulkano::memory::allocator::{AllocationCreateInfo, MemoryTypeFilter};
+use vulkano::DeviceSize;
+use vulkano::sync::GpuFuture;
+use crate::game_state::GameState;
+use crate::game_state::terrain::Chunk;
+use crate::game_state::terrain::chunk_data::ChunkData;
+use crate::graphics::{block_in_chunk_index, chunk_buffer_index, Graphics};
+
+impl Graphics {
+    /// Compares the distance field of the player's chunk to the CPU reference
+    /// and prints how many distances are too large, which lets rays skip blocks, or too small.
+    pub fn validate_distance_field(&mut self, game_state: &GameState) {
+        let chunk_position = game_state.get_player_chunk();
+        if !matches!(game_state.terrain.chunks.get(&chunk_position), Some(Chunk::Data(_))) {
+            println!("chunk {chunk_position} is uniform and has no distance field");
+            return;
+        }
+
+        let gpu_distances = self.read_distance_field(chunk_position);
+        let reference = self.reference_distance_field(game_state, chunk_position);
+
+        let too_large = gpu_distances.iter().zip(reference.iter()).filter(|(gpu, cpu)| gpu > cpu).count();
+        let too_small = gpu_distances.iter().zip(reference.iter()).filter(|(gpu, cpu)| gpu < cpu).count();
+        println!(
+            "distance field of chunk {chunk_position} ({:?}): {too_large} voxels too large, {too_small} too small of {}",
+            self.settings.graphics_settings.distance_field_generator,
+            reference.len(),
+        );
+    }
+
+    fn read_distance_field(&mut self, chunk_position: Vector3<i32>) -> Vec<u8> {
+        let buffer: Subbuffer<[u8]> = Buffer::new_slice(
+            self.vulkano_core.allocators.memory.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                ..Default::default()
+            },
+            ChunkData::volume(self.chunk_size()) as DeviceSize,
+        )
+            .unwrap();
+
+        let image = self.render_core.buffers.distance_data_buffers
+            [chunk_buffer_index(chunk_position, &self.settings)]
+            .clone();
+
+        let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
+            &self.vulkano_core.allocators.commmand_buffer,
+            self.vulkano_core.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        )
+            .unwrap();
+
+        builder
+            .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(image, buffer.clone()))
+            .unwrap();
+
+        let command_buffer = builder.build().unwrap();
+
+        self.wait_and_reset_last_frame_end();
+        let future = self
+            .previous_frame_end
+            .take()
+            .unwrap()
+            .then_execute(self.vulkano_core.queue.clone(), command_buffer)
+            .unwrap();
+        self.previous_frame_end = Some(future.boxed());
+        self.wait_and_reset_last_frame_end();
+
+        buffer.read().unwrap().to_vec()
+    }
+
+    /// Computes the distance field of a chunk on the CPU with the same padding and
+    /// treatment of unloaded chunks as the GPU generators.
+    fn reference_distance_field(&self, game_state: &GameState, chunk_position: Vector3<i32>) -> Vec<u8> {
+        let chunk_size = self.chunk_size() as i32;
+        let padding = Graphics::DISTANCE_FIELD_PADDING as i32;
+        let region_size = chunk_size + 2 * padding;
+        let region_origin = chunk_position * chunk_size - Vector3::repeat(padding);
+
+        let mut chunks = HashMap::new();
+        let mut solid = Vec::with_capacity(region_size.pow(3) as usize);
+        for z in 0..region_size {
+            for y in 0..region_size {
+                for x in 0..region_size {
+                    let position = region_origin + Vector3::new(x, y, z);
+                    let chunk = position.map(|x| x.div_euclid(chunk_size));
+                    if !self.is_chunk_loaded(chunk) {
+                        solid.push(true);
+                        continue;
+                    }
+                    let chunk_data = chunks.entry(chunk).or_insert_with(|| {
+                        game_state.terrain.chunks[&chunk].to_chunk_data(self.chunk_size())
+                    });
+                    solid.push(chunk_data.blocks()[block_in_chunk_index(position, self.chunk_size())] != 0);
+                }
+            }
+        }
+
+        let region = chebyshev_distance_field(
+            &chebyshev_seeds(&solid),
+            region_size as usize,
+            Graphics::DISTANCE_FIELD_PADDING as u8,
+        );
+
+        let mut distances = Vec::with_capacity(ChunkData::volume(self.chunk_size()));
+        for z in padding..padding + chunk_size {
+            for y in padding..padding + chunk_size {
+                for x in padding..padding + chunk_size {
+                    distances.push(region[(x + y * region_size + z * region_size * region_size) as usize]);
+                }
+            }
+        }
+        distances
+    }
+}
+
+/// Distance the empty voxels start with in the exact transform, see `FAR_DISTANCE` in util.glsl.
+pub const FAR_DISTANCE: u8 = u8::MAX;
+
+/// Initial distances of the exact transform as distance_field_setup.comp writes them for the Chebyshev generator.
+/// Only the solid voxels are marked, unlike for the sweeps.
+pub fn chebyshev_seeds(solid: &[bool]) -> Vec<u8> {
+    solid.iter().map(|&solid| if solid { 0 } else { FAR_DISTANCE }).collect()
+}
+
+/// Exact Chebyshev distance of every voxel of a cube to the closest voxel seeded with 0, capped at max_distance.
+/// Separable like the GPU generator, with one 1D transform per axis. The index is x + y * size + z * size².
+pub fn chebyshev_distance_field(seeds: &[u8], size: usize, max_distance: u8) -> Vec<u8> {
+    assert_eq!(seeds.len(), size.pow(3));
+    let mut distances = seeds.to_vec();
+
+    for stride in [1, size, size * size] {
+        let previous = distances.clone();
+        for (index, distance) in distances.iter_mut().enumerate() {
+            let position = index / stride % size;
+            let line_start = index - position * stride;
+            let first = position.saturating_sub(max_distance as usize);
+            let last = (position + max_distance as usize).min(size - 1);
+            *distance = (first..=last)
+                .map(|other| previous[line_start + other * stride].max(position.abs_diff(other) as u8))
+                .min()
+                .unwrap();
+        }
+    }
+
+    distances.iter().map(|&distance| distance.min(max_distance)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::graphics::allocators::Allocators;
+    use crate::graphics::render_core::pipelines::default_pipeline_from_shader_module;
+    use crate::settings::Settings;
+    use crate::shaders;
+    use vulkano::command_buffer::{AutoCommandBufferBuilder, CopyBufferToImageInfo, PrimaryCommandBufferAbstract};
+    use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+    use vulkano::device::{Device, DeviceCreateInfo, QueueCreateInfo, QueueFlags};
+    use vulkano::format::Format;
+    use vulkano::image::view::ImageView;
+    use vulkano::image::{Image, ImageCreateInfo, ImageType, ImageUsage};
+    use vulkano::instance::{Instance, InstanceCreateFlags, InstanceCreateInfo};
+    use vulkano::pipeline::{Pipeline, PipelineBindPoint};
+    use vulkano::VulkanLibrary;
+
+    fn brute_force_distance_field(solid: &[bool], size: usize, max_distance: u8) -> Vec<u8> {
+        let position = |index: usize| [index % size, index / size % size, index / size / size];
+        (0..solid.len())
+            .map(|index| {
+                solid
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, solid)| **solid)
+                    .map(|(other, _)| {
+                        let (a, b) = (position(index), position(other));
+                        (0..3).map(|axis| a[axis].abs_diff(b[axis])).max().unwrap()
+                    })
+                    .min()
+                    .unwrap_or(usize::MAX)
+                    .min(max_distance as usize) as u8
+            })
+            .collect()
+    }
+
+    /// xorshift, so the tests don't need a random number dependency
+    fn random_solids(size: usize, density: u32, mut seed: u32) -> Vec<bool> {
+        (0..size.pow(3))
+            .map(|_| {
+                seed ^= seed << 13;
+                seed ^= seed >> 17;
+                seed ^= seed << 5;
+                seed % 100 < density
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_brute_force_on_random_chunks() {
+        for (seed, density) in [(1, 1), (7, 5), (42, 20), (1234, 60)] {
+            let solid = random_solids(12, density, seed);
+            assert_eq!(
+                chebyshev_distance_field(&chebyshev_seeds(&solid), 12, 4),
+                brute_force_distance_field(&solid, 12, 4),
+                "seed {seed}, density {density}%"
+            );
+        }
+    }
+
+    /// Runs distance_field_chebyshev.comp on seeded random regions and compares the result with the brute force.
+    #[test]
+    #[ignore = "needs a Vulkan device"]
+    fn gpu_transform_matches_brute_force_on_random_chunks() {
+        const SIZE: u32 = 32; // a multiple of the workgroup size
+        let max_distance = Graphics::DISTANCE_FIELD_PADDING as u8;
+
+        let instance = Instance::new(VulkanLibrary::new().unwrap(), InstanceCreateInfo {
+            flags: InstanceCreateFlags::ENUMERATE_PORTABILITY,
+            ..Default::default()
+        })
+            .unwrap();
+        let (physical_device, queue_family_index) = instance
+            .enumerate_physical_devices()
+            .unwrap()
+            .find_map(|device| {
+                device
+                    .queue_family_properties()
+                    .iter()
+                    .position(|queue| queue.queue_flags.intersects(QueueFlags::COMPUTE))
+                    .map(|index| (device, index as u32))
+            })
+            .expect("no device with a compute queue");
+        let (device, mut queues) = Device::new(physical_device, DeviceCreateInfo {
+            queue_create_infos: vec![QueueCreateInfo { queue_family_index, ..Default::default() }],
+            ..Default::default()
+        })
+            .unwrap();
+        let queue = queues.next().unwrap();
+        let allocators = Allocators::new(device.clone());
+        let pipeline = default_pipeline_from_shader_module(
+            device.clone(),
+            shaders::distance_chebyshev::load(device.clone()).unwrap(),
+            &Settings::new().graphics_settings,
+        );
+
+        let region = Image::new(
+            allocators.memory.clone(),
+            ImageCreateInfo {
+                image_type: ImageType::Dim3d,
+                format: Format::R8_UINT,
+                extent: [SIZE; 3],
+                usage: ImageUsage::STORAGE | ImageUsage::TRANSFER_SRC | ImageUsage::TRANSFER_DST,
+                ..Default::default()
+            },
+            AllocationCreateInfo::default(),
+        )
+            .unwrap();
+        let descriptor_set = PersistentDescriptorSet::new(
+            &allocators.descriptor_set,
+            pipeline.layout().set_layouts()[0].clone(),
+            [WriteDescriptorSet::image_view(0, ImageView::new_default(region.clone()).unwrap())],
+            [],
+        )
+            .unwrap();
+
+        for (seed, density) in [(1, 1), (7, 5), (42, 20)] {
+            let solid = random_solids(SIZE as usize, density, seed);
+            let buffer = Buffer::from_iter(
+                allocators.memory.clone(),
+                BufferCreateInfo {
+                    usage: BufferUsage::TRANSFER_SRC | BufferUsage::TRANSFER_DST,
+                    ..Default::default()
+                },
+                AllocationCreateInfo {
+                    memory_type_filter: MemoryTypeFilter::PREFER_HOST | MemoryTypeFilter::HOST_RANDOM_ACCESS,
+                    ..Default::default()
+                },
+                chebyshev_seeds(&solid),
+            )
+                .unwrap();
+
+            let mut builder = AutoCommandBufferBuilder::primary(
+                &allocators.commmand_buffer,
+                queue_family_index,
+                CommandBufferUsage::OneTimeSubmit,
+            )
+                .unwrap();
+            builder
+                .copy_buffer_to_image(CopyBufferToImageInfo::buffer_image(buffer.clone(), region.clone()))
+                .unwrap()
+                .bind_pipeline_compute(pipeline.clone())
+                .unwrap()
+                .bind_descriptor_sets(PipelineBindPoint::Compute, pipeline.layout().clone(), 0, descriptor_set.clone())
+                .unwrap();
+            for axis in [Vector3::x(), Vector3::y(), Vector3::z()] {
+                builder
+                    .push_constants(pipeline.layout().clone(), 0, shaders::distance_chebyshev::PushConstants {
+                        axis,
+                        region_size: SIZE as i32,
+                    })
+                    .unwrap()
+                    .dispatch([SIZE / 16, SIZE / 16, 1])
+                    .unwrap();
+            }
+            builder
+                .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(region.clone(), buffer.clone()))
+                .unwrap();
+            builder
+                .build()
+                .unwrap()
+                .execute(queue.clone())
+                .unwrap()
+                .then_signal_fence_and_flush()
+                .unwrap()
+                .wait(None)
+                .unwrap();
+
+            // the generator caps the distances when storing them
+            let gpu_distances = buffer.read().unwrap().iter().map(|&distance| distance.min(max_distance)).collect::<Vec<_>>();
+            assert_eq!(
+                gpu_distances,
+                brute_force_distance_field(&solid, SIZE as usize, max_distance),
+                "seed {seed}, density {density}%"
+            );
+        }
+    }
+
+    #[test]
+    fn empty_region_is_capped() {
+        let distances = chebyshev_distance_field(&chebyshev_seeds(&[false; 8 * 8 * 8]), 8, 5);
+        assert!(distances.iter().all(|&distance| distance == 5));
+    }
+}
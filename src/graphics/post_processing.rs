@@ -0,0 +1,132 @@
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::descriptor_set::PersistentDescriptorSet;
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineBindPoint};
+use crate::graphics::Graphics;
+use crate::graphics::render_core::pipelines::post_processing_pipeline::chain_source;
+use crate::settings::graphics_settings::{PostProcessPass, Tonemapper};
+use crate::shaders::{bloom, sharpening, tonemap, vignette};
+
+impl Graphics {
+    /// Runs the configured post processing passes on the upscaled image and presents the result in the swapchain image.
+    pub(crate) fn record_post_processing(
+        &self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_index: usize,
+        image_extent: [u32; 2],
+    ) {
+        let graphics_settings = &self.settings.graphics_settings;
+        let post_processing_pipeline = &self.render_core.pipelines.post_processing_pipeline;
+        let full_resolution = [image_extent[0] / 16 + 1, image_extent[1] / 16 + 1, 1];
+        let passes = &graphics_settings.post_processing;
+
+        if graphics_settings.auto_exposure && passes.contains(&PostProcessPass::Tonemap) {
+            bind_pass(
+                builder,
+                &post_processing_pipeline.auto_exposure_pipeline,
+                &post_processing_pipeline.auto_exposure_descriptor_set,
+            );
+            builder.dispatch([1, 1, 1]).unwrap();
+        }
+
+        for (position, &pass) in passes.iter().enumerate() {
+            let pipeline = &post_processing_pipeline.pass_pipelines[pass as usize];
+            bind_pass(
+                builder,
+                pipeline,
+                &post_processing_pipeline.pass_descriptor_sets[pass as usize][chain_source(position)],
+            );
+            let layout = pipeline.layout().clone();
+            match pass {
+                PostProcessPass::Bloom => {
+                    let [width, height, _] = self.render_core.post_processing_images.bloom_images[0].extent();
+                    for stage in 0..4 {
+                        builder
+                            .push_constants(layout.clone(), 0, bloom::PushConstants {
+                                stage,
+                                threshold: graphics_settings.bloom_threshold,
+                                intensity: graphics_settings.bloom_intensity,
+                            })
+                            .unwrap();
+                        // only the last stage writes the full resolution image
+                        let workgroups = if stage == 3 { full_resolution } else { [width / 16 + 1, height / 16 + 1, 1] };
+                        builder.dispatch(workgroups).unwrap();
+                    }
+                    continue;
+                }
+                PostProcessPass::Tonemap => {
+                    builder
+                        .push_constants(layout, 0, tonemap::PushConstants {
+                            tonemapper: graphics_settings.tonemapper as u32,
+                            exposure: graphics_settings.exposure,
+                            use_auto_exposure: graphics_settings.auto_exposure as u32,
+                        })
+                        .unwrap();
+                }
+                PostProcessPass::ColorGrading => {}
+                PostProcessPass::Sharpening => {
+                    builder
+                        .push_constants(layout, 0, sharpening::PushConstants {
+                            strength: graphics_settings.sharpening_strength,
+                        })
+                        .unwrap();
+                }
+                PostProcessPass::Vignette => {
+                    builder
+                        .push_constants(layout, 0, vignette::PushConstants {
+                            strength: graphics_settings.vignette_strength,
+                        })
+                        .unwrap();
+                }
+            }
+            builder.dispatch(full_resolution).unwrap();
+        }
+
+        bind_pass(
+            builder,
+            &post_processing_pipeline.present_pipeline,
+            &post_processing_pipeline.present_descriptor_sets[chain_source(passes.len())][image_index],
+        );
+        builder.dispatch(full_resolution).unwrap();
+    }
+
+    /// Enables or disables a post processing pass, the chain is recorded every frame so no frame has to be waited for.
+    pub fn toggle_post_process_pass(&mut self, pass: PostProcessPass) -> bool {
+        self.settings.graphics_settings.toggle_post_process_pass(pass)
+    }
+
+    /// Switches between the tonemapping curves.
+    pub fn cycle_tonemapper(&mut self) -> Tonemapper {
+        let graphics_settings = &mut self.settings.graphics_settings;
+        graphics_settings.tonemapper = match graphics_settings.tonemapper {
+            Tonemapper::Reinhard => Tonemapper::Aces,
+            Tonemapper::Aces => Tonemapper::Reinhard,
+        };
+        graphics_settings.tonemapper
+    }
+
+    pub fn toggle_auto_exposure(&mut self) -> bool {
+        let graphics_settings = &mut self.settings.graphics_settings;
+        graphics_settings.auto_exposure = !graphics_settings.auto_exposure;
+        graphics_settings.auto_exposure
+    }
+
+    /// Brightens or darkens the image by the given number of stops.
+    pub fn change_exposure(&mut self, stops: f32) -> f32 {
+        let graphics_settings = &mut self.settings.graphics_settings;
+        graphics_settings.exposure = (graphics_settings.exposure + stops).clamp(-8., 8.);
+        graphics_settings.exposure
+    }
+}
+
+fn bind_pass(
+    builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+    pipeline: &Arc<ComputePipeline>,
+    descriptor_set: &Arc<PersistentDescriptorSet>,
+) {
+    builder
+        .bind_pipeline_compute(pipeline.clone())
+        .unwrap()
+        .bind_descriptor_sets(PipelineBindPoint::Compute, pipeline.layout().clone(), 0, descriptor_set.clone())
+        .unwrap();
+}
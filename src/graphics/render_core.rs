@@ -1,16 +1,19 @@
 use crate::graphics::buffers::Buffers;
 use crate::graphics::render_core::pipelines::Pipelines;
+use crate::graphics::render_core::post_processing_images::PostProcessingImages;
 use crate::graphics::render_core::swapchain_resources::SwapchainResources;
 use crate::graphics::vulkano_core::VulkanoCore;
 use crate::settings::Settings;
 
 pub mod pipelines;
+pub mod post_processing_images;
 pub mod swapchain_resources;
 
 pub struct RenderCore {
     pub swapchain_ressources: SwapchainResources,
     pub pipelines: Pipelines,
     pub buffers: Buffers,
+    pub post_processing_images: PostProcessingImages,
 }
 
 impl RenderCore {
@@ -18,17 +21,30 @@ impl RenderCore {
         let graphics_settings = &settings.graphics_settings;
         let buffers = Buffers::new(vulkano_core, &graphics_settings);
         let swapchain = SwapchainResources::new(vulkano_core);
+        let post_processing_images = PostProcessingImages::new(vulkano_core);
         let pipelines = Pipelines::new(
             vulkano_core.device.clone(),
             &swapchain.swapchain_images,
             vulkano_core,
             &buffers,
+            &post_processing_images,
             &settings.graphics_settings,
         );
         Self {
             swapchain_ressources: swapchain,
             buffers,
             pipelines,
+            post_processing_images,
         }
     }
+
+    /// Recreates everything that depends on the render distance.
+    pub(crate) fn recreate_chunk_resources(&mut self, vulkano_core: &VulkanoCore, settings: &Settings) {
+        self.buffers.recreate_chunk_images(vulkano_core, &settings.graphics_settings);
+        self.pipelines.recreate_chunk_pipelines(
+            vulkano_core,
+            &self.buffers,
+            &settings.graphics_settings,
+        );
+    }
 }
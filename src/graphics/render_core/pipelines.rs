@@ -1,25 +1,30 @@
+pub mod post_processing_pipeline;
 mod raytrace_pipeline;
 mod terrain_generator_pipeline;
 pub mod terrain_distance_pipeline;
 
 use crate::graphics::buffers::Buffers;
+use crate::graphics::render_core::pipelines::post_processing_pipeline::PostProcessingPipeline;
 use crate::graphics::render_core::pipelines::raytrace_pipeline::RaytracePipeline;
 use crate::graphics::render_core::pipelines::terrain_generator_pipeline::TerrainGeneratorPipeline;
+use crate::graphics::render_core::post_processing_images::PostProcessingImages;
 use crate::graphics::vulkano_core::VulkanoCore;
 use crate::settings::graphics_settings::GraphicsSettings;
+use std::collections::HashMap;
 use std::sync::Arc;
 use vulkano::device::Device;
 use vulkano::image::Image;
 use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineLayout, PipelineShaderStageCreateInfo};
 use vulkano::pipeline::compute::ComputePipelineCreateInfo;
 use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
-use vulkano::shader::ShaderModule;
+use vulkano::shader::{ShaderModule, SpecializationConstant};
 use crate::graphics::render_core::pipelines::terrain_distance_pipeline::TerrainDistancePipeline;
 
 pub struct Pipelines {
     pub raytrace_pipeline: RaytracePipeline,
     pub terrain_generator_pipeline: TerrainGeneratorPipeline,
     pub terrain_distance_pipeline: TerrainDistancePipeline,
+    pub post_processing_pipeline: PostProcessingPipeline,
 }
 
 impl Pipelines {
@@ -28,28 +33,64 @@ impl Pipelines {
         swapchain_images: &Vec<Arc<Image>>,
         vulkano: &VulkanoCore,
         buffers: &Buffers,
+        post_processing_images: &PostProcessingImages,
         graphics_settings: &GraphicsSettings,
     ) -> Self {
         Self {
-            raytrace_pipeline: RaytracePipeline::new(device.clone(), graphics_settings, swapchain_images, vulkano, buffers),
-            terrain_generator_pipeline: TerrainGeneratorPipeline::new(device.clone()),
-            terrain_distance_pipeline: TerrainDistancePipeline::new(device.clone()),
+            raytrace_pipeline: RaytracePipeline::new(device.clone(), graphics_settings, vulkano, buffers),
+            terrain_generator_pipeline: TerrainGeneratorPipeline::new(device.clone(), graphics_settings),
+            terrain_distance_pipeline: TerrainDistancePipeline::new(device.clone(), graphics_settings),
+            post_processing_pipeline: PostProcessingPipeline::new(
+                device.clone(),
+                swapchain_images,
+                vulkano,
+                buffers,
+                post_processing_images,
+            ),
         }
     }
 
+    /// Rebuilds the pipelines that depend on the render distances through specialization constants.
+    pub fn recreate_chunk_pipelines(
+        &mut self,
+        vulkano: &VulkanoCore,
+        buffers: &Buffers,
+        graphics_settings: &GraphicsSettings,
+    ) {
+        self.raytrace_pipeline = RaytracePipeline::new(vulkano.device.clone(), graphics_settings, vulkano, buffers);
+        self.terrain_distance_pipeline = TerrainDistancePipeline::new(vulkano.device.clone(), graphics_settings);
+    }
+
+    /// Has to be called after the swapchain and the window sized images were recreated.
     pub fn recreate_image_descriptor_sets(
         &mut self,
-        images: &Vec<Arc<Image>>,
+        swapchain_images: &Vec<Arc<Image>>,
         vulkano: &VulkanoCore,
         buffers: &Buffers,
+        post_processing_images: &PostProcessingImages,
     ) {
-        self.raytrace_pipeline.recreate_raytrace_descriptor_sets(images, vulkano, buffers);
+        self.raytrace_pipeline.recreate_descriptor_set(vulkano, buffers);
+        self.post_processing_pipeline.recreate_descriptor_sets(swapchain_images, vulkano, buffers, post_processing_images);
     }
 }
 
 
-pub fn default_pipeline_from_shader_module(device: Arc<Device>, module: Arc<ShaderModule>) -> Arc<ComputePipeline> {
+/// Specialization constants shared by all shaders, matching the `constant_id` layouts in the shaders.
+pub fn specialization_constants(graphics_settings: &GraphicsSettings) -> HashMap<u32, SpecializationConstant> {
+    [
+        (0, (graphics_settings.render_distance as i32).into()),
+        (1, (graphics_settings.chunk_size as i32).into()),
+        (2, (graphics_settings.vertical_render_distance as i32).into()),
+        (3, (graphics_settings.level_of_detail_layers as i32).into()),
+    ]
+        .into_iter()
+        .collect()
+}
+
+pub fn default_pipeline_from_shader_module(device: Arc<Device>, module: Arc<ShaderModule>, graphics_settings: &GraphicsSettings) -> Arc<ComputePipeline> {
     let entry_point = module
+        .specialize(specialization_constants(graphics_settings))
+        .unwrap()
         .entry_point("main")
         .unwrap();
     let stage_info = PipelineShaderStageCreateInfo::new(entry_point);
@@ -0,0 +1,228 @@
+use crate::graphics::buffers::Buffers;
+use crate::graphics::render_core::post_processing_images::PostProcessingImages;
+use crate::graphics::vulkano_core::VulkanoCore;
+use crate::settings::graphics_settings::PostProcessPass;
+use crate::shaders;
+use std::sync::Arc;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::Device;
+use vulkano::image::Image;
+use vulkano::image::view::ImageView;
+use vulkano::pipeline::compute::ComputePipelineCreateInfo;
+use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
+use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineLayout, PipelineShaderStageCreateInfo};
+use vulkano::shader::ShaderModule;
+
+/// Number of images a pass can read from: the upscaled image and the two intermediate images.
+const SOURCE_IMAGES: usize = 3;
+
+/// Post processing chain turning the HDR image of the raytracer into the swapchain image.
+pub struct PostProcessingPipeline {
+    /// scales the traced image to the window before the chain starts
+    pub upscale_pipeline: Arc<ComputePipeline>,
+    /// the one at index i reads history image i and writes the other one
+    pub upscale_descriptor_sets: [Arc<PersistentDescriptorSet>; 2],
+    pub auto_exposure_pipeline: Arc<ComputePipeline>,
+    pub auto_exposure_descriptor_set: Arc<PersistentDescriptorSet>,
+    /// indexed by `PostProcessPass`
+    pub pass_pipelines: Vec<Arc<ComputePipeline>>,
+    /// for every pass one descriptor set per image it can read from, see `chain_source`
+    pub pass_descriptor_sets: Vec<[Arc<PersistentDescriptorSet>; SOURCE_IMAGES]>,
+    /// encodes the result of the chain as sRGB into the swapchain image
+    pub present_pipeline: Arc<ComputePipeline>,
+    /// for every image the chain can end in one descriptor set per swapchain image
+    pub present_descriptor_sets: [Vec<Arc<PersistentDescriptorSet>>; SOURCE_IMAGES],
+}
+
+/// Image the pass at the given position of the chain reads from, 0 is the upscaled image and 1 and 2 are the
+/// intermediate images. Each pass writes to the intermediate image the next one reads from, so the position after the
+/// last pass gives the image the chain ends in.
+pub fn chain_source(position: usize) -> usize {
+    if position == 0 {
+        0
+    } else {
+        1 + (position - 1) % 2
+    }
+}
+
+impl PostProcessingPipeline {
+    pub fn new(
+        device: Arc<Device>,
+        swapchain_images: &Vec<Arc<Image>>,
+        vulkano: &VulkanoCore,
+        buffers: &Buffers,
+        images: &PostProcessingImages,
+    ) -> Self {
+        let upscale_pipeline = pipeline_from_shader_module(device.clone(), shaders::upscale::load(device.clone()).unwrap());
+        let auto_exposure_pipeline = pipeline_from_shader_module(device.clone(), shaders::auto_exposure::load(device.clone()).unwrap());
+        let pass_pipelines = PostProcessPass::ALL
+            .iter()
+            .map(|&pass| pipeline_from_shader_module(device.clone(), pass_shader_module(device.clone(), pass)))
+            .collect();
+        let present_pipeline = pipeline_from_shader_module(device.clone(), shaders::present::load(device.clone()).unwrap());
+
+        let mut pipeline = Self {
+            upscale_descriptor_sets: create_upscale_descriptor_sets(&upscale_pipeline, vulkano, buffers, images),
+            upscale_pipeline,
+            auto_exposure_descriptor_set: create_auto_exposure_descriptor_set(&auto_exposure_pipeline, vulkano, buffers),
+            auto_exposure_pipeline,
+            pass_pipelines,
+            pass_descriptor_sets: vec![],
+            present_pipeline,
+            present_descriptor_sets: Default::default(),
+        };
+        pipeline.recreate_descriptor_sets(swapchain_images, vulkano, buffers, images);
+        pipeline
+    }
+
+    /// Has to be called whenever the HDR image, the post processing images or the swapchain images changed.
+    pub fn recreate_descriptor_sets(
+        &mut self,
+        swapchain_images: &Vec<Arc<Image>>,
+        vulkano: &VulkanoCore,
+        buffers: &Buffers,
+        images: &PostProcessingImages,
+    ) {
+        let source_views: [Arc<ImageView>; SOURCE_IMAGES] = [
+            images.upscaled_image.clone(),
+            images.intermediate_images[0].clone(),
+            images.intermediate_images[1].clone(),
+        ]
+            .map(|image| ImageView::new_default(image).unwrap());
+
+        self.upscale_descriptor_sets = create_upscale_descriptor_sets(&self.upscale_pipeline, vulkano, buffers, images);
+        self.auto_exposure_descriptor_set = create_auto_exposure_descriptor_set(&self.auto_exposure_pipeline, vulkano, buffers);
+
+        self.pass_descriptor_sets = PostProcessPass::ALL
+            .iter()
+            .map(|&pass| {
+                std::array::from_fn(|source| {
+                    // the pass writes to the intermediate image it doesn't read from
+                    let target = 1 + source % 2;
+                    let mut writes = vec![
+                        WriteDescriptorSet::image_view(0, source_views[source].clone()),
+                        WriteDescriptorSet::image_view(1, source_views[target].clone()),
+                    ];
+                    writes.extend(pass_descriptor_writes(pass, buffers, images));
+                    PersistentDescriptorSet::new(
+                        &vulkano.allocators.descriptor_set,
+                        self.pass_pipelines[pass as usize].layout().set_layouts()[0].clone(),
+                        writes,
+                        [],
+                    )
+                        .unwrap()
+                })
+            })
+            .collect();
+
+        self.present_descriptor_sets = std::array::from_fn(|source| {
+            swapchain_images
+                .iter()
+                .map(|image| {
+                    PersistentDescriptorSet::new(
+                        &vulkano.allocators.descriptor_set,
+                        self.present_pipeline.layout().set_layouts()[0].clone(),
+                        [
+                            WriteDescriptorSet::image_view(0, source_views[source].clone()),
+                            WriteDescriptorSet::image_view(1, ImageView::new_default(image.clone()).unwrap()),
+                        ],
+                        [],
+                    )
+                        .unwrap()
+                })
+                .collect()
+        });
+    }
+}
+
+fn pass_shader_module(device: Arc<Device>, pass: PostProcessPass) -> Arc<ShaderModule> {
+    match pass {
+        PostProcessPass::Bloom => shaders::bloom::load(device),
+        PostProcessPass::Tonemap => shaders::tonemap::load(device),
+        PostProcessPass::ColorGrading => shaders::color_grading::load(device),
+        PostProcessPass::Sharpening => shaders::sharpening::load(device),
+        PostProcessPass::Vignette => shaders::vignette::load(device),
+    }
+        .unwrap()
+}
+
+/// Bindings of the pass after its source and target image.
+fn pass_descriptor_writes(
+    pass: PostProcessPass,
+    buffers: &Buffers,
+    images: &PostProcessingImages,
+) -> Vec<WriteDescriptorSet> {
+    match pass {
+        PostProcessPass::Bloom => vec![
+            WriteDescriptorSet::image_view(2, ImageView::new_default(images.bloom_images[0].clone()).unwrap()),
+            WriteDescriptorSet::image_view(3, ImageView::new_default(images.bloom_images[1].clone()).unwrap()),
+        ],
+        PostProcessPass::Tonemap => vec![WriteDescriptorSet::buffer(2, buffers.auto_exposure_buffer.clone())],
+        PostProcessPass::ColorGrading => vec![WriteDescriptorSet::image_view_sampler(
+            2,
+            buffers.textures.color_grading_lut.clone(),
+            buffers.textures.lut_sampler.clone(),
+        )],
+        PostProcessPass::Sharpening | PostProcessPass::Vignette => vec![],
+    }
+}
+
+fn create_upscale_descriptor_sets(
+    upscale_pipeline: &Arc<ComputePipeline>,
+    vulkano: &VulkanoCore,
+    buffers: &Buffers,
+    images: &PostProcessingImages,
+) -> [Arc<PersistentDescriptorSet>; 2] {
+    let history_views = images.history_images.clone().map(|image| ImageView::new_default(image).unwrap());
+    std::array::from_fn(|history| {
+        PersistentDescriptorSet::new(
+            &vulkano.allocators.descriptor_set,
+            upscale_pipeline.layout().set_layouts()[0].clone(),
+            [
+                WriteDescriptorSet::image_view(0, ImageView::new_default(buffers.hdr_image.clone()).unwrap()),
+                WriteDescriptorSet::image_view(1, history_views[history].clone()),
+                WriteDescriptorSet::image_view(2, ImageView::new_default(images.upscaled_image.clone()).unwrap()),
+                WriteDescriptorSet::image_view(3, history_views[1 - history].clone()),
+            ],
+            [],
+        )
+            .unwrap()
+    })
+}
+
+fn create_auto_exposure_descriptor_set(
+    auto_exposure_pipeline: &Arc<ComputePipeline>,
+    vulkano: &VulkanoCore,
+    buffers: &Buffers,
+) -> Arc<PersistentDescriptorSet> {
+    PersistentDescriptorSet::new(
+        &vulkano.allocators.descriptor_set,
+        auto_exposure_pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::image_view(0, ImageView::new_default(buffers.hdr_image.clone()).unwrap()),
+            WriteDescriptorSet::buffer(1, buffers.auto_exposure_buffer.clone()),
+        ],
+        [],
+    )
+        .unwrap()
+}
+
+/// The post processing shaders don't use the specialization constants of the chunk shaders.
+fn pipeline_from_shader_module(device: Arc<Device>, module: Arc<ShaderModule>) -> Arc<ComputePipeline> {
+    let stage_info = PipelineShaderStageCreateInfo::new(module.entry_point("main").unwrap());
+
+    let layout = PipelineLayout::new(
+        device.clone(),
+        PipelineDescriptorSetLayoutCreateInfo::from_stages(&[stage_info.clone()])
+            .into_pipeline_layout_create_info(device.clone())
+            .unwrap()
+    )
+        .unwrap();
+
+    ComputePipeline::new(
+        device.clone(),
+        None,
+        ComputePipelineCreateInfo::stage_layout(stage_info, layout),
+    )
+        .unwrap()
+}
@@ -4,7 +4,6 @@ use crate::shaders::rendering::PushConstants;
 use std::sync::Arc;
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::device::Device;
-use vulkano::image::Image;
 use vulkano::image::view::ImageView;
 use vulkano::pipeline::compute::ComputePipelineCreateInfo;
 use vulkano::pipeline::layout::{
@@ -13,29 +12,25 @@ use vulkano::pipeline::layout::{
 use vulkano::pipeline::{ComputePipeline, Pipeline, PipelineLayout, PipelineShaderStageCreateInfo};
 use vulkano::shader::ShaderStages;
 use crate::graphics::buffers::Buffers;
+use crate::graphics::render_core::pipelines::specialization_constants;
 use crate::graphics::vulkano_core::VulkanoCore;
 
 pub struct RaytracePipeline {
     pub pipeline: Arc<ComputePipeline>,
-    pub descriptor_sets: Vec<Arc<PersistentDescriptorSet>>,
+    pub descriptor_set: Arc<PersistentDescriptorSet>,
 }
 
 impl RaytracePipeline {
     pub fn new(
         device: Arc<Device>,
         graphics_settings: &GraphicsSettings,
-        images: &Vec<Arc<Image>>,
         vulkano: &VulkanoCore,
         buffers: &Buffers,
     ) -> Self {
         let compute_shader = shaders::rendering::load(device.clone()).unwrap();
 
         let entry_point = compute_shader
-            .specialize(
-                [(0, (graphics_settings.render_distance as i32).into())]
-                    .into_iter()
-                    .collect(),
-            )
+            .specialize(specialization_constants(graphics_settings))
             .unwrap()
             .entry_point("main")
             .unwrap();
@@ -62,44 +57,47 @@ impl RaytracePipeline {
             ComputePipelineCreateInfo::stage_layout(stage_info, layout),
         )
             .unwrap();
-        
-        let mut ret = Self{
+
+        let descriptor_set = create_descriptor_set(&pipeline, vulkano, buffers);
+        Self{
             pipeline,
-            descriptor_sets: vec!()
-        };
-        ret.recreate_raytrace_descriptor_sets(images, vulkano, buffers);
-        
-        ret
+            descriptor_set,
+        }
     }
-    
-    
-    
-    pub fn recreate_raytrace_descriptor_sets(
-        &mut self,
-        images: &Vec<Arc<Image>>,
-        vulkano: &VulkanoCore,
-        buffers: &Buffers,
-    ) {
-        self.descriptor_sets = images
-            .iter()
-            .map(|x| {
-                PersistentDescriptorSet::new(
-                    &vulkano.allocators.descriptor_set,
-                    self.pipeline.layout().set_layouts()[0].clone(),
-                    [
-                        WriteDescriptorSet::image_view(0, ImageView::new_default(x.clone()).unwrap()),
-                        WriteDescriptorSet::buffer(1, buffers.player_raycast_buffer.clone()),
-                        WriteDescriptorSet::buffer(2, buffers.gpu_graphics_settings_buffer.clone()),
-                        WriteDescriptorSet::image_view_array(3, 0, buffers.get_chunk_image_views()),
-                        WriteDescriptorSet::image_view_array(4, 0, buffers.get_distance_image_views()),
-                        WriteDescriptorSet::image_view(5, buffers.textures.image_view.clone()),
-                        WriteDescriptorSet::sampler(6, buffers.textures.sampler.clone()),
-                    ],
-                    [],
-                )
-                    .unwrap()
-            })
-            .collect()
+
+    /// Has to be called when one of the bound images is recreated.
+    pub fn recreate_descriptor_set(&mut self, vulkano: &VulkanoCore, buffers: &Buffers) {
+        self.descriptor_set = create_descriptor_set(&self.pipeline, vulkano, buffers);
     }
+}
 
+fn create_descriptor_set(
+    pipeline: &Arc<ComputePipeline>,
+    vulkano: &VulkanoCore,
+    buffers: &Buffers,
+) -> Arc<PersistentDescriptorSet> {
+    PersistentDescriptorSet::new(
+        &vulkano.allocators.descriptor_set,
+        pipeline.layout().set_layouts()[0].clone(),
+        [
+            WriteDescriptorSet::image_view(0, ImageView::new_default(buffers.hdr_image.clone()).unwrap()),
+            WriteDescriptorSet::buffer(1, buffers.player_raycast_buffer.clone()),
+            WriteDescriptorSet::buffer(2, buffers.gpu_graphics_settings_buffer.clone()),
+            WriteDescriptorSet::image_view_array(3, 0, buffers.get_chunk_image_views()),
+            WriteDescriptorSet::image_view_array(4, 0, buffers.get_distance_image_views()),
+            WriteDescriptorSet::image_view(5, buffers.textures.image_view.clone()),
+            WriteDescriptorSet::sampler(6, buffers.textures.sampler.clone()),
+            WriteDescriptorSet::image_view(7, buffers.textures.normal_view.clone()),
+            WriteDescriptorSet::buffer(8, buffers.chunk_info_buffer.clone()),
+            WriteDescriptorSet::image_view_array(9, 0, buffers.get_brick_occupancy_image_views()),
+            WriteDescriptorSet::buffer(10, buffers.traversal_stats_buffer.clone()),
+            WriteDescriptorSet::image_view_array(11, 0, buffers.get_lod_chunk_image_views()),
+            WriteDescriptorSet::buffer(12, buffers.lod_chunk_info_buffer.clone()),
+            WriteDescriptorSet::buffer(13, buffers.chunk_lights_buffer.clone()),
+            WriteDescriptorSet::buffer(14, buffers.block_materials_buffer.clone()),
+            WriteDescriptorSet::image_view(15, ImageView::new_default(buffers.accumulation_image.clone()).unwrap()),
+        ],
+        [],
+    )
+        .unwrap()
 }
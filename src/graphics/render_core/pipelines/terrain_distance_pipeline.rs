@@ -8,49 +8,134 @@ use vulkano::pipeline::{ComputePipeline, Pipeline};
 use vulkano::sync::GpuFuture;
 use crate::graphics::{chunk_buffer_index, Graphics};
 use crate::graphics::render_core::pipelines::default_pipeline_from_shader_module;
+use crate::settings::graphics_settings::{DistanceFieldGenerator, GraphicsSettings};
 use crate::shaders;
 
 pub struct TerrainDistancePipeline {
     pub sweep_pipeline: Arc<ComputePipeline>,
+    pub chebyshev_pipeline: Arc<ComputePipeline>,
     pub setup_pipeline: Arc<ComputePipeline>,
+    pub store_pipeline: Arc<ComputePipeline>,
+    pub occupancy_pipeline: Arc<ComputePipeline>,
 }
 
 impl TerrainDistancePipeline {
-    pub fn new(device: Arc<Device>) -> Self {
+    pub fn new(device: Arc<Device>, graphics_settings: &GraphicsSettings) -> Self {
         TerrainDistancePipeline{
-            sweep_pipeline: sweep_pipeline(device.clone()),
-            setup_pipeline: setup_pipeline(device.clone()),
+            sweep_pipeline: sweep_pipeline(device.clone(), graphics_settings),
+            chebyshev_pipeline: chebyshev_pipeline(device.clone(), graphics_settings),
+            setup_pipeline: setup_pipeline(device.clone(), graphics_settings),
+            store_pipeline: store_pipeline(device.clone(), graphics_settings),
+            occupancy_pipeline: occupancy_pipeline(device.clone(), graphics_settings),
         }
     }
 }
 
-fn sweep_pipeline(device: Arc<Device>) -> Arc<ComputePipeline> {
+fn sweep_pipeline(device: Arc<Device>, graphics_settings: &GraphicsSettings) -> Arc<ComputePipeline> {
     let compute_shader = shaders::distance_gen::load(device.clone()).unwrap();
     
-    default_pipeline_from_shader_module(device.clone(), compute_shader)
+    default_pipeline_from_shader_module(device.clone(), compute_shader, graphics_settings)
 }
 
 
-fn setup_pipeline(device: Arc<Device>) -> Arc<ComputePipeline> {
+fn chebyshev_pipeline(device: Arc<Device>, graphics_settings: &GraphicsSettings) -> Arc<ComputePipeline> {
+    let compute_shader = shaders::distance_chebyshev::load(device.clone()).unwrap();
+
+    default_pipeline_from_shader_module(device.clone(), compute_shader, graphics_settings)
+}
+
+
+fn setup_pipeline(device: Arc<Device>, graphics_settings: &GraphicsSettings) -> Arc<ComputePipeline> {
     let compute_shader = shaders::distance_setup::load(device.clone()).unwrap();
 
-    default_pipeline_from_shader_module(device.clone(), compute_shader)
+    default_pipeline_from_shader_module(device.clone(), compute_shader, graphics_settings)
+}
+
+
+fn store_pipeline(device: Arc<Device>, graphics_settings: &GraphicsSettings) -> Arc<ComputePipeline> {
+    let compute_shader = shaders::distance_store::load(device.clone()).unwrap();
+
+    default_pipeline_from_shader_module(device.clone(), compute_shader, graphics_settings)
+}
+
+
+fn occupancy_pipeline(device: Arc<Device>, graphics_settings: &GraphicsSettings) -> Arc<ComputePipeline> {
+    let compute_shader = shaders::brick_occupancy::load(device.clone()).unwrap();
+
+    default_pipeline_from_shader_module(device.clone(), compute_shader, graphics_settings)
+}
+
+
+/// Box of the world a distance field pass runs over.
+struct DistanceRegion {
+    /// world position of the first voxel of the scratch region
+    origin: Vector3<i32>,
+    /// edge length of the scratch region, a multiple of 16
+    size: u32,
+    /// world position of the first voxel written back into the chunks
+    store_origin: Vector3<i32>,
+    store_size: u32,
 }
 
+/// Edge length of the region updated by `execute_local`, covering every voxel within twice the padding of the block.
+const LOCAL_REGION_SIZE: u32 = 4 * Graphics::DISTANCE_FIELD_PADDING + 16;
+
+/// Edge length of the scratch image, large enough for both full and local updates.
+pub fn scratch_region_size(chunk_size: u32) -> u32 {
+    (chunk_size + 2 * Graphics::DISTANCE_FIELD_PADDING).max(LOCAL_REGION_SIZE)
+}
 
 pub fn execute(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
+    let padding = Graphics::DISTANCE_FIELD_PADDING as i32;
+    let chunk_origin = chunk_position * graphics.chunk_size() as i32;
+    let region = DistanceRegion {
+        origin: chunk_origin - Vector3::repeat(padding),
+        size: graphics.chunk_size() + 2 * Graphics::DISTANCE_FIELD_PADDING,
+        store_origin: chunk_origin,
+        store_size: graphics.chunk_size(),
+    };
+
     graphics.wait_and_reset_last_frame_end();
-    execute_setup(graphics, chunk_position);
-    execute_sweeps(graphics, chunk_position);
+    execute_region(graphics, &region);
+    execute_occupancy(graphics, chunk_position);
+}
+
+/// Updates the distances after a single block changed. Since stored distances are capped at the padding,
+/// only voxels within the padding of the block can change, which may lie in neighbouring chunks.
+/// Only loaded chunks are written, a loaded block chunk has to already have a distance field.
+pub fn execute_local(graphics: &mut Graphics, block_position: Vector3<i32>) {
+    let padding = Graphics::DISTANCE_FIELD_PADDING as i32;
+    let region = DistanceRegion {
+        origin: block_position - Vector3::repeat(2 * padding),
+        size: LOCAL_REGION_SIZE,
+        store_origin: block_position - Vector3::repeat(padding),
+        store_size: 2 * Graphics::DISTANCE_FIELD_PADDING + 1,
+    };
+
+    execute_region(graphics, &region);
+    let block_chunk = block_position.map(|x| x.div_euclid(graphics.chunk_size() as i32));
+    if graphics.is_chunk_loaded(block_chunk) {
+        execute_occupancy(graphics, block_chunk);
+    }
 }
 
-fn execute_setup(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
+fn execute_region(graphics: &mut Graphics, region: &DistanceRegion) {
+    execute_setup(graphics, region);
+    match graphics.settings.graphics_settings.distance_field_generator {
+        DistanceFieldGenerator::Sweep => execute_sweeps(graphics, region),
+        DistanceFieldGenerator::Chebyshev => execute_chebyshev(graphics, region),
+    }
+    execute_store(graphics, region);
+}
+
+fn execute_occupancy(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
+    let chunk_index = chunk_buffer_index(chunk_position, &graphics.settings);
     let descriptor_set = PersistentDescriptorSet::new(
         &graphics.vulkano_core.allocators.descriptor_set,
         graphics.render_core
             .pipelines
             .terrain_distance_pipeline
-            .setup_pipeline
+            .occupancy_pipeline
             .layout()
             .set_layouts()[0]
             .clone(),
@@ -58,13 +143,13 @@ fn execute_setup(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
             WriteDescriptorSet::image_view(
                 0,
                 ImageView::new_default(
-                    graphics.render_core.buffers.block_data_buffers[chunk_buffer_index(chunk_position, &graphics.settings)].clone()
+                    graphics.render_core.buffers.block_data_buffers[chunk_index].clone()
                 ).unwrap()
             ),
             WriteDescriptorSet::image_view(
                 1,
                 ImageView::new_default(
-                    graphics.render_core.buffers.distance_data_buffers[chunk_buffer_index(chunk_position, &graphics.settings)].clone()
+                    graphics.render_core.buffers.brick_occupancy_buffers[chunk_index].clone()
                 ).unwrap()
             )
         ],
@@ -79,6 +164,78 @@ fn execute_setup(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
     )
         .unwrap();
 
+    builder
+        .bind_pipeline_compute(
+            graphics.render_core
+                .pipelines
+                .terrain_distance_pipeline
+                .occupancy_pipeline
+                .clone(),
+        )
+        .unwrap()
+        .bind_descriptor_sets(
+            vulkano::pipeline::PipelineBindPoint::Compute,
+            graphics.render_core
+                .pipelines
+                .terrain_distance_pipeline
+                .occupancy_pipeline
+                .layout()
+                .clone(),
+            0,
+            descriptor_set,
+        )
+        .unwrap()
+        .dispatch([graphics.chunk_size() / Graphics::BRICK_SIZE / 2; 3])
+        .unwrap();
+
+    let command_buffer = builder.build().unwrap();
+
+    let future = graphics
+        .previous_frame_end
+        .take()
+        .unwrap()
+        .then_execute(graphics.vulkano_core.queue.clone(), command_buffer)
+        .unwrap();
+
+    graphics.previous_frame_end = Some(future.boxed());
+}
+
+fn execute_setup(graphics: &mut Graphics, region: &DistanceRegion) {
+    let generator = graphics.settings.graphics_settings.distance_field_generator;
+    let push_constants = shaders::distance_setup::PushConstants {
+        region_origin: region.origin.into(),
+        exact_seeds: (generator == DistanceFieldGenerator::Chebyshev) as u32,
+        loaded_area_center: graphics.loaded_area_center.into(),
+    };
+
+    let descriptor_set = PersistentDescriptorSet::new(
+        &graphics.vulkano_core.allocators.descriptor_set,
+        graphics.render_core
+            .pipelines
+            .terrain_distance_pipeline
+            .setup_pipeline
+            .layout()
+            .set_layouts()[0]
+            .clone(),
+        [
+            WriteDescriptorSet::image_view_array(0, 0, graphics.render_core.buffers.get_chunk_image_views()),
+            WriteDescriptorSet::buffer(1, graphics.render_core.buffers.chunk_info_buffer.clone()),
+            WriteDescriptorSet::image_view(
+                2,
+                ImageView::new_default(graphics.render_core.buffers.distance_region_buffer.clone()).unwrap()
+            )
+        ],
+        [],
+    )
+        .unwrap();
+
+    let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
+        &graphics.vulkano_core.allocators.commmand_buffer,
+        graphics.vulkano_core.queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+
     builder
         .bind_pipeline_compute(
             graphics.render_core
@@ -88,6 +245,17 @@ fn execute_setup(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
                 .clone(),
         )
         .unwrap()
+        .push_constants(
+            graphics.render_core
+                .pipelines
+                .terrain_distance_pipeline
+                .setup_pipeline
+                .layout()
+                .clone(),
+            0,
+            push_constants,
+        )
+        .unwrap()
         .bind_descriptor_sets(
             vulkano::pipeline::PipelineBindPoint::Compute,
             graphics.render_core
@@ -100,7 +268,7 @@ fn execute_setup(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
             descriptor_set,
         )
         .unwrap()
-        .dispatch([Graphics::CHUNK_SIZE / 8; 3])
+        .dispatch([region.size / 8; 3])
         .unwrap();
 
     let command_buffer = builder.build().unwrap();
@@ -115,7 +283,7 @@ fn execute_setup(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
     graphics.previous_frame_end = Some(future.boxed());
 }
 
-fn execute_sweeps(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
+fn execute_sweeps(graphics: &mut Graphics, region: &DistanceRegion) {
     let descriptor_set = PersistentDescriptorSet::new(
         &graphics.vulkano_core.allocators.descriptor_set,
         graphics.render_core
@@ -128,15 +296,7 @@ fn execute_sweeps(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
         [
             WriteDescriptorSet::image_view(
                 0,
-                ImageView::new_default(
-                    graphics.render_core.buffers.block_data_buffers[chunk_buffer_index(chunk_position, &graphics.settings)].clone()
-                ).unwrap()
-            ),
-            WriteDescriptorSet::image_view(
-                1,
-                ImageView::new_default(
-                    graphics.render_core.buffers.distance_data_buffers[chunk_buffer_index(chunk_position, &graphics.settings)].clone()
-                ).unwrap()
+                ImageView::new_default(graphics.render_core.buffers.distance_region_buffer.clone()).unwrap()
             )
         ],
         [],
@@ -188,11 +348,14 @@ fn execute_sweeps(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
             .push_constants(
                 graphics.render_core.pipelines.terrain_distance_pipeline.sweep_pipeline.layout().clone(),
                 0,
-                *dir,
+                shaders::distance_gen::PushConstants {
+                    sweep_direction: *dir,
+                    region_size: region.size as i32,
+                },
             )
             .unwrap()
             .dispatch(
-                [Graphics::CHUNK_SIZE / 16, Graphics::CHUNK_SIZE / 16, 1]
+                [region.size / 16, region.size / 16, 1]
             ).unwrap();
     }
 
@@ -206,4 +369,164 @@ fn execute_sweeps(graphics: &mut Graphics, chunk_position: Vector3<i32>) {
         .unwrap();
 
     graphics.previous_frame_end = Some(future.boxed());
-}
\ No newline at end of file
+}
+
+fn execute_chebyshev(graphics: &mut Graphics, region: &DistanceRegion) {
+    let descriptor_set = PersistentDescriptorSet::new(
+        &graphics.vulkano_core.allocators.descriptor_set,
+        graphics.render_core
+            .pipelines
+            .terrain_distance_pipeline
+            .chebyshev_pipeline
+            .layout()
+            .set_layouts()[0]
+            .clone(),
+        [
+            WriteDescriptorSet::image_view(
+                0,
+                ImageView::new_default(graphics.render_core.buffers.distance_region_buffer.clone()).unwrap()
+            )
+        ],
+        [],
+    )
+        .unwrap();
+
+    let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
+        &graphics.vulkano_core.allocators.commmand_buffer,
+        graphics.vulkano_core.queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+
+    builder
+        .bind_pipeline_compute(
+            graphics.render_core
+                .pipelines
+                .terrain_distance_pipeline
+                .chebyshev_pipeline
+                .clone(),
+        )
+        .unwrap()
+        .bind_descriptor_sets(
+            vulkano::pipeline::PipelineBindPoint::Compute,
+            graphics.render_core
+                .pipelines
+                .terrain_distance_pipeline
+                .chebyshev_pipeline
+                .layout()
+                .clone(),
+            0,
+            descriptor_set,
+        )
+        .unwrap();
+
+    for axis in [Vector3::x(), Vector3::y(), Vector3::z()] {
+        builder
+            .push_constants(
+                graphics.render_core.pipelines.terrain_distance_pipeline.chebyshev_pipeline.layout().clone(),
+                0,
+                shaders::distance_chebyshev::PushConstants {
+                    axis,
+                    region_size: region.size as i32,
+                },
+            )
+            .unwrap()
+            .dispatch([region.size / 16, region.size / 16, 1])
+            .unwrap();
+    }
+
+    let command_buffer = builder.build().unwrap();
+
+    let future = graphics
+        .previous_frame_end
+        .take()
+        .unwrap()
+        .then_execute(graphics.vulkano_core.queue.clone(), command_buffer)
+        .unwrap();
+
+    graphics.previous_frame_end = Some(future.boxed());
+}
+
+/// Copies the distances of the store box out of the scratch region into the chunks it overlaps.
+fn execute_store(graphics: &mut Graphics, region: &DistanceRegion) {
+    let push_constants = shaders::distance_store::PushConstants {
+        region_origin: region.origin.into(),
+        store_size: region.store_size as i32,
+        store_origin: region.store_origin.into(),
+        loaded_area_center: graphics.loaded_area_center.into(),
+    };
+
+    let descriptor_set = PersistentDescriptorSet::new(
+        &graphics.vulkano_core.allocators.descriptor_set,
+        graphics.render_core
+            .pipelines
+            .terrain_distance_pipeline
+            .store_pipeline
+            .layout()
+            .set_layouts()[0]
+            .clone(),
+        [
+            WriteDescriptorSet::image_view(
+                0,
+                ImageView::new_default(graphics.render_core.buffers.distance_region_buffer.clone()).unwrap()
+            ),
+            WriteDescriptorSet::image_view_array(1, 0, graphics.render_core.buffers.get_distance_image_views()),
+            WriteDescriptorSet::buffer(2, graphics.render_core.buffers.chunk_info_buffer.clone()),
+        ],
+        [],
+    )
+        .unwrap();
+
+    let mut builder = vulkano::command_buffer::AutoCommandBufferBuilder::primary(
+        &graphics.vulkano_core.allocators.commmand_buffer,
+        graphics.vulkano_core.queue.queue_family_index(),
+        CommandBufferUsage::OneTimeSubmit,
+    )
+        .unwrap();
+
+    builder
+        .bind_pipeline_compute(
+            graphics.render_core
+                .pipelines
+                .terrain_distance_pipeline
+                .store_pipeline
+                .clone(),
+        )
+        .unwrap()
+        .push_constants(
+            graphics.render_core
+                .pipelines
+                .terrain_distance_pipeline
+                .store_pipeline
+                .layout()
+                .clone(),
+            0,
+            push_constants,
+        )
+        .unwrap()
+        .bind_descriptor_sets(
+            vulkano::pipeline::PipelineBindPoint::Compute,
+            graphics.render_core
+                .pipelines
+                .terrain_distance_pipeline
+                .store_pipeline
+                .layout()
+                .clone(),
+            0,
+            descriptor_set,
+        )
+        .unwrap()
+        .dispatch([region.store_size.div_ceil(8); 3])
+        .unwrap();
+
+    let command_buffer = builder.build().unwrap();
+
+    let future = graphics
+        .previous_frame_end
+        .take()
+        .unwrap()
+        .then_execute(graphics.vulkano_core.queue.clone(), command_buffer)
+        .unwrap();
+
+    graphics.previous_frame_end = Some(future.boxed());
+}
@@ -1,3 +1,5 @@
+use crate::graphics::render_core::pipelines::specialization_constants;
+use crate::settings::graphics_settings::GraphicsSettings;
 use crate::shaders;
 use crate::shaders::terrain_gen::PushConstants;
 use std::sync::Arc;
@@ -13,9 +15,13 @@ pub struct TerrainGeneratorPipeline {
     pub pipeline: Arc<ComputePipeline>,
 }
 impl TerrainGeneratorPipeline {
-    pub fn new(device: Arc<Device>) -> Self{
+    pub fn new(device: Arc<Device>, graphics_settings: &GraphicsSettings) -> Self{
         let compute_shader = shaders::terrain_gen::load(device.clone()).unwrap();
-        let entry_point = compute_shader.entry_point("main").unwrap();
+        let entry_point = compute_shader
+            .specialize(specialization_constants(graphics_settings))
+            .unwrap()
+            .entry_point("main")
+            .unwrap();
         let stage_info = PipelineShaderStageCreateInfo::new(entry_point);
 
         let layout = PipelineLayout::new(
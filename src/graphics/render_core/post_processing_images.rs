@@ -0,0 +1,46 @@
+use crate::graphics::vulkano_core::VulkanoCore;
+use std::sync::Arc;
+use vulkano::format::Format;
+use vulkano::image::{Image, ImageCreateInfo, ImageUsage};
+use vulkano::memory::allocator::AllocationCreateInfo;
+
+/// Images the post processing passes write their results to, sized like the window and recreated with the swapchain.
+pub struct PostProcessingImages {
+    /// traced image upscaled to the window, the post processing chain starts with it
+    pub upscaled_image: Arc<Image>,
+    /// upscaled images of the last two frames, the temporal upscaling blends the older one into the current image
+    pub history_images: [Arc<Image>; 2],
+    /// the passes alternate between reading one and writing the other
+    pub intermediate_images: [Arc<Image>; 2],
+    /// half resolution images the bloom pass blurs the bright pixels in
+    pub bloom_images: [Arc<Image>; 2],
+}
+
+impl PostProcessingImages {
+    pub fn new(vulkano_core: &VulkanoCore) -> Self {
+        let [width, height]: [u32; 2] = vulkano_core.window.inner_size().into();
+        let extent = [width.max(1), height.max(1)];
+        let bloom_extent = extent.map(|length| length.div_ceil(2));
+        Self {
+            upscaled_image: create_intermediate_image(vulkano_core, extent),
+            history_images: [(); 2].map(|_| create_intermediate_image(vulkano_core, extent)),
+            intermediate_images: [(); 2].map(|_| create_intermediate_image(vulkano_core, extent)),
+            bloom_images: [(); 2].map(|_| create_intermediate_image(vulkano_core, bloom_extent)),
+        }
+    }
+}
+
+fn create_intermediate_image(vulkano_core: &VulkanoCore, [width, height]: [u32; 2]) -> Arc<Image> {
+    Image::new(
+        vulkano_core.allocators.memory.clone(),
+        ImageCreateInfo {
+            image_type: vulkano::image::ImageType::Dim2d,
+            format: Format::R16G16B16A16_SFLOAT,
+            extent: [width, height, 1],
+            usage: ImageUsage::STORAGE,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
+    )
+        .unwrap()
+}
@@ -1,4 +1,5 @@
 use crate::graphics::Graphics;
+use crate::graphics::render_core::post_processing_images::PostProcessingImages;
 use crate::graphics::vulkano_core::VulkanoCore;
 use std::sync::Arc;
 use vulkano::format::Format;
@@ -32,6 +33,11 @@ impl SwapchainResources {
             })
             .expect("failed to recreate swapchain");
 
+        graphics
+            .render_core
+            .buffers
+            .recreate_render_sized_images(&graphics.vulkano_core, &graphics.settings.graphics_settings);
+        graphics.render_core.post_processing_images = PostProcessingImages::new(&graphics.vulkano_core);
         graphics
             .render_core
             .pipelines
@@ -39,9 +45,11 @@ impl SwapchainResources {
                 &new_images,
                 &graphics.vulkano_core,
                 &graphics.render_core.buffers,
+                &graphics.render_core.post_processing_images,
             );
 
         swapchain_resources.swapchain = new_swapchain;
+        swapchain_resources.swapchain_images = new_images;
         swapchain_resources.recreate_swapchain = false;
     }
 }
@@ -1,6 +1,6 @@
 use std::sync::Arc;
 use nalgebra::Vector3;
-use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
+use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage};
 use vulkano::command_buffer::{BufferImageCopy, CommandBufferUsage, CopyBufferToImageInfo, PrimaryCommandBufferAbstract};
 use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::image::{Image, ImageAspects, ImageSubresourceLayers};
@@ -9,36 +9,41 @@ use vulkano::pipeline::Pipeline;
 use vulkano::{sync, DeviceSize};
 use vulkano::sync::GpuFuture;
 use crate::game_state::GameState;
-use crate::game_state::terrain::{ChunkBuffer, ChunkData};
+use crate::game_state::terrain::{Chunk, ChunkBuffer};
+use crate::game_state::terrain::chunk_data;
+use crate::game_state::terrain::chunk_data::ChunkData;
 use crate::graphics::{block_in_chunk_index, Graphics};
 use crate::shaders::terrain_gen;
 
 impl Graphics {
+    /// Copies a chunk buffer into its image, only_copy_blocks limits the copy to the given blocks.
     pub fn copy_buffer_to_image(
         &mut self,
         buffer: ChunkBuffer,
         image: Arc<Image>,
-        only_copy_single_block: Option<Vector3<i32>>,
+        only_copy_blocks: Option<&[Vector3<i32>]>,
     ) {
-        let copy_info = match only_copy_single_block {
+        let copy_info = match only_copy_blocks {
             None => CopyBufferToImageInfo::buffer_image(buffer.clone(), image.clone()),
-            Some(block_position) => {
-                let block_in_chunk = block_in_chunk_index(block_position);
-                let regions = vec![BufferImageCopy {
-                    buffer_offset: (block_in_chunk * size_of::<u16>()) as DeviceSize,
-                    image_subresource: ImageSubresourceLayers {
-                        aspects: ImageAspects::COLOR,
-                        mip_level: 0,
-                        array_layers: 0..1,
-                    },
-                    image_offset: block_position
-                        .map(|x| x.rem_euclid(Self::CHUNK_SIZE as i32) as u32)
-                        .data
-                        .0[0],
-                    image_extent: [1; 3],
-                    ..Default::default()
-                }]
-                    .into();
+            Some(block_positions) => {
+                let chunk_size = self.chunk_size();
+                let regions = block_positions
+                    .iter()
+                    .map(|block_position| BufferImageCopy {
+                        buffer_offset: (block_in_chunk_index(*block_position, chunk_size) * size_of::<u16>()) as DeviceSize,
+                        image_subresource: ImageSubresourceLayers {
+                            aspects: ImageAspects::COLOR,
+                            mip_level: 0,
+                            array_layers: 0..1,
+                        },
+                        image_offset: block_position
+                            .map(|x| x.rem_euclid(chunk_size as i32) as u32)
+                            .data
+                            .0[0],
+                        image_extent: [1; 3],
+                        ..Default::default()
+                    })
+                    .collect();
 
                 CopyBufferToImageInfo {
                     regions,
@@ -75,49 +80,39 @@ impl Graphics {
             return;
         }
         self.wait_and_reset_last_frame_end();
+        self.reset_accumulation();
+        self.loaded_area_center = game_state.get_player_chunk();
 
-        let gen_dist = self.settings.graphics_settings.render_distance as i32;
-        for x in (-gen_dist)..=gen_dist {
-            for y in (-gen_dist)..=gen_dist {
-                for z in (-gen_dist)..=gen_dist {
-                    self.wait_and_reset_last_frame_end();
-                    let chunk_pos =
-                        (game_state.get_player_chunk() + Vector3::new(x, y, z)) as Vector3<i32>;
-                    //update this chunk if there was no previous chunk or if it left the players chunk range
-                    let update_chunk = match old_chunk_pos {
-                        None => true,
-                        Some(old_pos) => (chunk_pos - old_pos).amax() > gen_dist,
-                    };
-
-                    if update_chunk {
-                        game_state.terrain.upload_chunk(self, chunk_pos);
-                    }
-                }
+        let gen_dist = self.settings.graphics_settings.render_distances();
+        let updated_chunks = chunks_entering_range(game_state.get_player_chunk(), old_chunk_pos, gen_dist);
+        for chunk_pos in updated_chunks.iter() {
+            self.wait_and_reset_last_frame_end();
+            game_state.terrain.upload_chunk(self, *chunk_pos);
+        }
+        // distance fields read the neighbouring chunks, so all blocks have to be uploaded first
+        for chunk_pos in updated_chunks {
+            game_state.terrain.update_distance_field(self, chunk_pos);
+        }
+
+        for level in 1..self.settings.graphics_settings.level_of_detail_layers as u32 {
+            let center = lod_chunk_containing(game_state.get_player_chunk(), level);
+            let old_center = old_chunk_pos.map(|old_pos| lod_chunk_containing(old_pos, level));
+            for lod_chunk_pos in chunks_entering_range(center, old_center, gen_dist) {
+                game_state.terrain.upload_lod_chunk(self, level, lod_chunk_pos);
             }
         }
+        game_state.terrain.evict_chunks(self);
         self.previous_frame_end = Some(sync::now(self.vulkano_core.device.clone()).boxed());
     }
 
-    /// Generates a chunk and returns a host-mapped Buffer containing its Data
-    pub fn generate_chunk(&mut self, chunk_position: Vector3<i32>) -> ChunkBuffer {
-        let cpu_buffer: ChunkBuffer = Buffer::new_sized(
-            self.vulkano_core.allocators.memory.clone(),
-            BufferCreateInfo {
-                usage: BufferUsage::TRANSFER_SRC
-                    | BufferUsage::TRANSFER_DST
-                    | BufferUsage::STORAGE_BUFFER,
-                ..Default::default()
-            },
-            AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_HOST
-                    | MemoryTypeFilter::HOST_RANDOM_ACCESS,
-                ..Default::default()
-            },
-        )
-            .unwrap();
+    /// Generates a chunk of the given level of detail and waits for it. The blocks are only copied into a host buffer
+    /// of their own if they aren't all the same.
+    pub fn generate_chunk(&mut self, level: u32, chunk_position: Vector3<i32>) -> Chunk {
+        let generation_buffer = self.render_core.buffers.chunk_generation_buffer.clone();
 
         let push_constants = terrain_gen::PushConstants {
             chunk_position: chunk_position.into(),
+            level,
         };
 
         let descriptor_set = PersistentDescriptorSet::new(
@@ -129,7 +124,7 @@ impl Graphics {
                 .layout()
                 .set_layouts()[0]
                 .clone(),
-            [WriteDescriptorSet::buffer(0, cpu_buffer.clone())],
+            [WriteDescriptorSet::buffer(0, generation_buffer.clone())],
             [],
         )
             .unwrap();
@@ -173,7 +168,7 @@ impl Graphics {
                 descriptor_set,
             )
             .unwrap()
-            .dispatch([Self::CHUNK_SIZE / 8; 3])
+            .dispatch([self.chunk_size() / 8; 3])
             .unwrap();
 
         let command_buffer = builder.build().unwrap();
@@ -186,15 +181,61 @@ impl Graphics {
             .unwrap();
 
         self.previous_frame_end = Some(future.boxed());
-        cpu_buffer
+        self.wait_and_reset_last_frame_end();
+
+        let blocks = generation_buffer.read().unwrap();
+        match chunk_data::uniform_block(&blocks) {
+            Some(block) => Chunk::Uniform(block),
+            None => Chunk::Data(self.chunk_buffer_from_blocks(&blocks)),
+        }
     }
     
+    /// Tells the raytracer whether the chunk image at chunk_index can be skipped.
+    /// Has to be called while the gpu is idle.
+    pub fn set_chunk_info(&mut self, chunk_index: usize, chunk: &Chunk) {
+        self.render_core.buffers.set_chunk_info(chunk_index, chunk);
+    }
+
+    /// Tells the raytracer which blocks of the chunk at chunk_index emit light.
+    /// Has to be called while the gpu is idle.
+    pub fn set_chunk_lights(&mut self, chunk_index: usize, chunk: &Chunk) {
+        let chunk_size = self.chunk_size();
+        self.render_core.buffers.set_chunk_lights(chunk_index, chunk, chunk_size);
+    }
+
+    /// Like `set_chunk_info` for the chunks of the coarser levels of detail.
+    pub fn set_lod_chunk_info(&mut self, lod_chunk_index: usize, chunk: &Chunk) {
+        self.render_core.buffers.set_lod_chunk_info(lod_chunk_index, chunk);
+    }
+
+    pub fn is_chunk_loaded(&self, chunk_position: Vector3<i32>) -> bool {
+        self.is_lod_chunk_loaded(0, chunk_position)
+    }
+
+    /// Whether a chunk of the given level of detail, covering 2^level chunks along each axis, has an image.
+    pub fn is_lod_chunk_loaded(&self, level: u32, lod_chunk_position: Vector3<i32>) -> bool {
+        (lod_chunk_position - lod_chunk_containing(self.loaded_area_center, level))
+            .iter()
+            .zip(self.settings.graphics_settings.render_distances().iter())
+            .all(|(offset, dist)| offset.abs() <= *dist)
+    }
+
     pub fn generate_distance_field(&mut self, chunk_position: Vector3<i32>) {
         crate::graphics::render_core::pipelines::terrain_distance_pipeline::execute(self, chunk_position);
     }
 
+    /// Updates the distance fields around a changed block instead of regenerating whole chunks.
+    pub fn update_distance_field_around(&mut self, block_position: Vector3<i32>) {
+        crate::graphics::render_core::pipelines::terrain_distance_pipeline::execute_local(self, block_position);
+    }
+
     pub fn chunk_from_data(&mut self, chunk_data: ChunkData) -> ChunkBuffer {
-        Buffer::from_data(
+        assert_eq!(chunk_data.size(), self.chunk_size());
+        self.chunk_buffer_from_blocks(chunk_data.blocks())
+    }
+
+    fn chunk_buffer_from_blocks(&self, blocks: &[u16]) -> ChunkBuffer {
+        Buffer::from_iter(
             self.vulkano_core.allocators.memory.clone(),
             BufferCreateInfo {
                 usage: BufferUsage::TRANSFER_SRC
@@ -207,8 +248,40 @@ impl Graphics {
                     | MemoryTypeFilter::HOST_RANDOM_ACCESS,
                 ..Default::default()
             },
-            chunk_data
+            blocks.iter().copied(),
         )
             .unwrap()
     }
-}
\ No newline at end of file
+}
+/// Position of the chunk of a level of detail that contains the given full resolution chunk.
+pub fn lod_chunk_containing(chunk_position: Vector3<i32>, level: u32) -> Vector3<i32> {
+    chunk_position.map(|x| x >> level)
+}
+
+/// Chunks within range of center that were not in range of old_center, all of them if there is no old center.
+fn chunks_entering_range(
+    center: Vector3<i32>,
+    old_center: Option<Vector3<i32>>,
+    range: Vector3<i32>,
+) -> Vec<Vector3<i32>> {
+    let mut chunks = Vec::new();
+    for x in (-range.x)..=range.x {
+        for y in (-range.y)..=range.y {
+            for z in (-range.z)..=range.z {
+                let chunk_pos = center + Vector3::new(x, y, z);
+                //update this chunk if there was no previous chunk or if it left the players chunk range
+                let entering = match old_center {
+                    None => true,
+                    Some(old_pos) => (chunk_pos - old_pos)
+                        .iter()
+                        .zip(range.iter())
+                        .any(|(offset, dist)| offset.abs() > *dist),
+                };
+                if entering {
+                    chunks.push(chunk_pos);
+                }
+            }
+        }
+    }
+    chunks
+}
@@ -0,0 +1,52 @@
+use crate::game_state::GameState;
+use crate::graphics::Graphics;
+use crate::shaders::rendering::TraversalStats;
+
+impl Graphics {
+    /// Renders the current view once without and once with brick skipping
+    /// and prints the average number of traversal steps per pixel of both.
+    pub fn benchmark_traversal(&mut self, game_state: &GameState) {
+        let brick_skipping = self.settings.graphics_settings.brick_skipping;
+        let mut average_steps = [0.; 2];
+        for (enabled, average) in [false, true].into_iter().zip(average_steps.iter_mut()) {
+            self.set_brick_skipping(enabled);
+            let (rays, steps) = self.measure_traversal(game_state);
+            if rays == 0 {
+                println!("traversal benchmark failed: no frame was rendered");
+                self.set_brick_skipping(brick_skipping);
+                return;
+            }
+            *average = steps as f64 / rays as f64;
+        }
+        self.set_brick_skipping(brick_skipping);
+
+        println!(
+            "average traversal steps per pixel: {:.2} distance field only, {:.2} with brick skipping ({:.1}%)",
+            average_steps[0],
+            average_steps[1],
+            average_steps[1] / average_steps[0] * 100.
+        );
+    }
+
+    pub fn set_brick_skipping(&mut self, enabled: bool) {
+        self.wait_and_reset_last_frame_end();
+        self.settings.graphics_settings.brick_skipping = enabled;
+        self.render_core.buffers.gpu_graphics_settings_buffer.write().unwrap().brick_skipping = enabled as u32;
+    }
+
+    /// Draws a single frame while counting traversal steps and returns the number of rays and steps.
+    fn measure_traversal(&mut self, game_state: &GameState) -> (u64, u64) {
+        self.wait_and_reset_last_frame_end();
+        *self.render_core.buffers.traversal_stats_buffer.write().unwrap() = TraversalStats {
+            enabled: 1,
+            ..TraversalStats::new()
+        };
+
+        self.draw_frame(game_state);
+        self.wait_and_reset_last_frame_end();
+
+        let mut stats = self.render_core.buffers.traversal_stats_buffer.write().unwrap();
+        stats.enabled = 0;
+        (stats.rays as u64, stats.steps as u64)
+    }
+}
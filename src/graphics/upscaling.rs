@@ -0,0 +1,169 @@
+use nalgebra::Matrix4;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer};
+use vulkano::pipeline::{Pipeline, PipelineBindPoint};
+use crate::graphics::Graphics;
+use crate::settings::graphics_settings::{GraphicsSettings, Upscaling};
+use crate::shaders::rendering::PushConstants;
+use crate::shaders::upscale;
+
+/// Frames after which the jitter offsets repeat.
+const JITTER_SEQUENCE_LENGTH: u32 = 8;
+
+impl Graphics {
+    /// Upscaling of this frame. Accumulating already averages the frames with rays spread over their pixels,
+    /// so it falls back to the bilinear upscaling.
+    fn active_upscaling(&self) -> Upscaling {
+        if self.settings.graphics_settings.accumulation {
+            Upscaling::Bilinear
+        } else {
+            self.settings.graphics_settings.upscaling
+        }
+    }
+
+    /// Offset of the rays within their pixels in this frame, the temporal upscaling combines the offsets of
+    /// consecutive frames.
+    pub(crate) fn upscaling_jitter(&self) -> [f32; 2] {
+        match self.active_upscaling() {
+            Upscaling::Bilinear => [0.; 2],
+            Upscaling::Temporal => jitter(self.upscaled_frames),
+        }
+    }
+
+    /// Scales the traced image to the window, has to be recorded right after the raytracer.
+    pub(crate) fn record_upscaling(
+        &mut self,
+        builder: &mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>,
+        image_extent: [u32; 2],
+        push_constants: &PushConstants,
+    ) {
+        let graphics_settings = &self.settings.graphics_settings;
+        let post_processing_pipeline = &self.render_core.pipelines.post_processing_pipeline;
+        let upscale_pipeline = &post_processing_pipeline.upscale_pipeline;
+        let history = (self.upscaled_frames % 2) as usize;
+        let cam_transform: [[f32; 4]; 4] = push_constants.cam_transform.into();
+        builder
+            .bind_pipeline_compute(upscale_pipeline.clone())
+            .unwrap()
+            .push_constants(
+                upscale_pipeline.layout().clone(),
+                0,
+                upscale::PushConstants {
+                    reprojection: reprojection(&cam_transform.into(), &self.previous_cam_transform.into()).into(),
+                    jitter: push_constants.jitter,
+                    tan_half_fov: (graphics_settings.field_of_view.to_radians() / 2.).tan(),
+                    upscaling: self.active_upscaling() as u32,
+                    history_valid: self.upscaling_history_valid as u32,
+                },
+            )
+            .unwrap()
+            .bind_descriptor_sets(
+                PipelineBindPoint::Compute,
+                upscale_pipeline.layout().clone(),
+                0,
+                post_processing_pipeline.upscale_descriptor_sets[history].clone(),
+            )
+            .unwrap()
+            .dispatch([image_extent[0] / 16 + 1, image_extent[1] / 16 + 1, 1])
+            .unwrap();
+
+        self.previous_cam_transform = cam_transform;
+        self.upscaled_frames = self.upscaled_frames.wrapping_add(1);
+        self.upscaling_history_valid = true;
+    }
+
+    /// Changes the fraction of the window resolution the rays are traced at,
+    /// the images are resized with the swapchain before the next frame.
+    pub fn change_render_scale(&mut self, change: f32) -> f32 {
+        let graphics_settings = &mut self.settings.graphics_settings;
+        graphics_settings.render_scale =
+            (graphics_settings.render_scale + change).clamp(GraphicsSettings::MIN_RENDER_SCALE, 1.);
+        let render_scale = graphics_settings.render_scale;
+        self.recreate_swapchain();
+        render_scale
+    }
+
+    pub fn toggle_upscaling(&mut self) -> Upscaling {
+        let graphics_settings = &mut self.settings.graphics_settings;
+        graphics_settings.upscaling = match graphics_settings.upscaling {
+            Upscaling::Bilinear => Upscaling::Temporal,
+            Upscaling::Temporal => Upscaling::Bilinear,
+        };
+        self.upscaling_history_valid = false;
+        graphics_settings.upscaling
+    }
+}
+
+/// Transform from the view space of the camera to the one of the previous camera, which is all the reprojection needs
+/// and keeps the push constants small. Camera transforms hold the position and the transposed rotation,
+/// see `GameState::get_push_constants`.
+fn reprojection(cam_transform: &Matrix4<f32>, previous_cam_transform: &Matrix4<f32>) -> Matrix4<f32> {
+    let previous_rotation = previous_cam_transform.fixed_view::<3, 3>(0, 0);
+    let rotation = previous_rotation * cam_transform.fixed_view::<3, 3>(0, 0).transpose();
+    let translation =
+        previous_rotation * (cam_transform.fixed_view::<3, 1>(0, 3) - previous_cam_transform.fixed_view::<3, 1>(0, 3));
+    let mut reprojection = Matrix4::identity();
+    reprojection.fixed_view_mut::<3, 3>(0, 0).copy_from(&rotation);
+    reprojection.fixed_view_mut::<3, 1>(0, 3).copy_from(&translation);
+    reprojection
+}
+
+/// Halton sequence of the bases 2 and 3 centered on the pixel, spreading the offsets of consecutive frames evenly.
+fn jitter(frame: u32) -> [f32; 2] {
+    let index = frame % JITTER_SEQUENCE_LENGTH + 1;
+    [halton(index, 2) - 0.5, halton(index, 3) - 0.5]
+}
+
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut fraction = 1.;
+    let mut result = 0.;
+    while index > 0 {
+        fraction /= base as f32;
+        result += fraction * (index % base) as f32;
+        index /= base;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_halton() {
+        let base_2: Vec<f32> = (1..=4).map(|index| halton(index, 2)).collect();
+        assert_eq!(base_2, [0.5, 0.25, 0.75, 0.125]);
+        assert!((halton(1, 3) - 1. / 3.).abs() < 1e-6);
+        assert!((halton(4, 3) - 4. / 9.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_jitter_stays_within_pixel_and_repeats() {
+        let offsets: Vec<[f32; 2]> = (0..JITTER_SEQUENCE_LENGTH).map(jitter).collect();
+        for (i, offset) in offsets.iter().enumerate() {
+            assert!(offset.iter().all(|&component| (-0.5..0.5).contains(&component)));
+            assert!(offsets[..i].iter().all(|other| other != offset));
+        }
+        assert_eq!(jitter(JITTER_SEQUENCE_LENGTH), jitter(0));
+    }
+
+    #[test]
+    fn test_reprojection_matches_world_space() {
+        use nalgebra::{Rotation3, Vector3};
+        // built like GameState::get_push_constants, returns the camera transform and the rotation of the view
+        let camera = |position: Vector3<f32>, yaw: f32, pitch: f32| {
+            let rotation = Rotation3::from_scaled_axis(Vector3::new(0., yaw, 0.))
+                * Rotation3::from_scaled_axis(Vector3::new(pitch, 0., 0.));
+            (Matrix4::new_translation(&position) * Matrix4::from(rotation).transpose(), rotation)
+        };
+        let (cam_transform, rotation) = camera(Vector3::new(3., 70., -2.), 0.4, -0.2);
+        let (previous_cam_transform, previous_rotation) = camera(Vector3::new(2.5, 70.2, -1.), 0.3, -0.1);
+
+        let view_position = Vector3::new(0.3, -0.2, 1.).normalize() * 12.;
+        // the same steps as the reprojection did in world space before
+        let world_position = Vector3::new(3., 70., -2.) + rotation * view_position;
+        let expected = previous_rotation.inverse() * (world_position - Vector3::new(2.5, 70.2, -1.));
+
+        let reprojected = reprojection(&cam_transform, &previous_cam_transform) * view_position.push(1.);
+        assert!((reprojected.xyz() - expected).norm() < 1e-4);
+    }
+}
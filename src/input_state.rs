@@ -1,6 +1,6 @@
 use crate::input_state::keyboard_input_state::KeyboardInputState;
 use crate::input_state::mouse_input_state::MouseInputState;
-use winit::event::{ElementState, KeyboardInput, MouseButton};
+use winit::event::{ElementState, KeyboardInput, MouseButton, MouseScrollDelta};
 mod keyboard_input_state;
 mod mouse_input_state;
 
@@ -71,6 +71,10 @@ impl InputState {
         self.mouse.update(delta);
     }
 
+    pub fn update_scroll(&mut self, delta: MouseScrollDelta) {
+        self.mouse.update_scroll(delta);
+    }
+
     pub fn refresh(&mut self) {
         self.keyboard.refresh();
         self.mouse.refresh();
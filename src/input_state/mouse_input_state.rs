@@ -1,10 +1,14 @@
 use std::collections::HashSet;
-use winit::event::MouseButton;
+use winit::event::{MouseButton, MouseScrollDelta};
 
 #[derive(Debug)]
 pub struct MouseInputState {
     pub delta_x: f64,
     pub delta_y: f64,
+    /// whole lines scrolled since the last frame, positive when scrolling up
+    pub scroll_steps: i32,
+    /// part of a line scrolled so far, trackpads send many small pixel deltas that only step once they add up to a line
+    scroll_remainder: f32,
     pub pressed_buttons: HashSet<MouseButton>,
     pub last_frame_pressed_buttons: HashSet<MouseButton>,
 }
@@ -13,6 +17,8 @@ impl MouseInputState {
         Self {
             delta_x: 0.0,
             delta_y: 0.0,
+            scroll_steps: 0,
+            scroll_remainder: 0.0,
             pressed_buttons: HashSet::new(),
             last_frame_pressed_buttons: HashSet::new(),
         }
@@ -21,9 +27,41 @@ impl MouseInputState {
         self.delta_x += delta_x;
         self.delta_y += delta_y;
     }
+    pub fn update_scroll(&mut self, delta: MouseScrollDelta) {
+        const PIXELS_PER_LINE: f64 = 30.;
+        self.scroll_remainder += match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(position) => (position.y / PIXELS_PER_LINE) as f32,
+        };
+        let lines = self.scroll_remainder.trunc();
+        self.scroll_steps += lines as i32;
+        self.scroll_remainder -= lines;
+    }
     pub fn refresh(&mut self) {
         self.delta_x = 0.;
         self.delta_y = 0.;
+        self.scroll_steps = 0;
         self.last_frame_pressed_buttons = self.pressed_buttons.clone();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use winit::dpi::PhysicalPosition;
+
+    #[test]
+    fn test_pixel_scrolling_adds_up_to_lines() {
+        let mut mouse = MouseInputState::new();
+        for _ in 0..5 {
+            mouse.update_scroll(MouseScrollDelta::PixelDelta(PhysicalPosition::new(0., 5.)));
+        }
+        assert_eq!(mouse.scroll_steps, 0);
+        mouse.update_scroll(MouseScrollDelta::PixelDelta(PhysicalPosition::new(0., 10.)));
+        assert_eq!(mouse.scroll_steps, 1);
+
+        mouse.refresh();
+        mouse.update_scroll(MouseScrollDelta::LineDelta(0., -2.));
+        assert_eq!(mouse.scroll_steps, -2);
+    }
+}
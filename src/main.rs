@@ -9,11 +9,10 @@ mod textures;
 
 use crate::game_state::GameState;
 use crate::game_state::terrain::block::Block;
-use crate::game_state::terrain::block::solid_block::SolidBlock;
 use crate::input_state::{InputState, PressState};
 use crate::settings::Settings;
+use crate::settings::graphics_settings::PostProcessPass;
 use graphics::Graphics;
-use nalgebra::Vector3;
 use std::time::Instant;
 use winit::event::MouseButton;
 
@@ -21,7 +20,7 @@ fn main() {
     let settings = Settings::new();
 
     let (mut graphics, event_loop) = Graphics::new(settings);
-    //let game_state = GameState::new();
+    //let game_state = GameState::new(graphics.chunk_size());
     let game_state = GameState::load("saves/test.voxmap", &mut graphics);
     let input_state = InputState::new();
 
@@ -41,6 +40,55 @@ fn main() {
             println!("toggling cursor confine");
             graphics.toggle_confine();
         }
+        change_render_distance(game_state, graphics, input_state);
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::F3, PressState::Down) {
+            graphics.benchmark_traversal(game_state);
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::F4, PressState::Down) {
+            graphics.validate_distance_field(game_state);
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::F5, PressState::Down) {
+            println!("{}", game_state.terrain.statistics());
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::F6, PressState::Down) {
+            // a running time of day moves the sun, which restarts the accumulation every frame
+            println!("toggling accumulation, pause the time of day with T for still images");
+            graphics.toggle_accumulation();
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::F7, PressState::Down) {
+            println!("toggling placement preview");
+            graphics.toggle_placement_preview();
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::F8, PressState::Down) {
+            println!("tonemapper: {:?}", graphics.cycle_tonemapper());
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::F9, PressState::Down) {
+            println!("auto exposure: {}", graphics.toggle_auto_exposure());
+        }
+        for (key, pass) in [
+            (winit::event::VirtualKeyCode::F10, PostProcessPass::Bloom),
+            (winit::event::VirtualKeyCode::F11, PostProcessPass::Sharpening),
+            (winit::event::VirtualKeyCode::F12, PostProcessPass::Vignette),
+        ] {
+            if input_state.is_key_pressed(key, PressState::Down) {
+                println!("{:?}: {}", pass, graphics.toggle_post_process_pass(pass));
+            }
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::LBracket, PressState::Down) {
+            println!("render scale: {}", graphics.change_render_scale(-0.125));
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::RBracket, PressState::Down) {
+            println!("render scale: {}", graphics.change_render_scale(0.125));
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::Backslash, PressState::Down) {
+            println!("upscaling: {:?}", graphics.toggle_upscaling());
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::Equals, PressState::Down) {
+            println!("exposure: {} stops", graphics.change_exposure(0.5));
+        }
+        if input_state.is_key_pressed(winit::event::VirtualKeyCode::Minus, PressState::Down) {
+            println!("exposure: {} stops", graphics.change_exposure(-0.5));
+        }
         player_actions(game_state, graphics, input_state);
 
         if input_state.is_key_pressed(winit::event::VirtualKeyCode::P, PressState::Down) {
@@ -57,17 +105,43 @@ fn player_actions(game_state: &mut GameState, graphics: &mut Graphics, input_sta
     if input_state.is_mouse_pressed(MouseButton::Left, PressState::Held)
         && let Some(block_hit) = graphics.what_is_bro_looking_at()
     {
-        let pos = ((block_hit.hit_point + block_hit.hit_normal * 0.2).map(|x| x.floor() as i32))
-            as Vector3<i32>;
-        game_state
-            .terrain
-            .place_block(graphics, pos, Block::SolidBlock(SolidBlock::Stone))
+        let block = game_state.player.selected_block();
+        game_state.terrain.place_block(block_hit.placement_block(), block)
     }
     if input_state.is_mouse_pressed(MouseButton::Right, PressState::Held)
         && let Some(block_hit) = graphics.what_is_bro_looking_at()
     {
-        let pos = (block_hit.hit_point - block_hit.hit_normal * 0.2).map(|x| x.floor() as i32)
-            as Vector3<i32>;
-        game_state.terrain.place_block(graphics, pos, Block::Air)
+        game_state.terrain.place_block(block_hit.target_block(), Block::Air)
+    }
+    if input_state.is_mouse_pressed(MouseButton::Middle, PressState::Down)
+        && let Some(block_hit) = graphics.what_is_bro_looking_at()
+    {
+        game_state
+            .player
+            .pick_block(Block::from_u16(block_hit.block_id as u16));
     }
-}
\ No newline at end of file
+}
+
+fn change_render_distance(game_state: &mut GameState, graphics: &mut Graphics, input_state: &InputState) {
+    use winit::event::VirtualKeyCode;
+    let key_change = |increase: VirtualKeyCode, decrease: VirtualKeyCode| {
+        input_state.is_key_pressed(increase, PressState::Down) as i32
+            - input_state.is_key_pressed(decrease, PressState::Down) as i32
+    };
+    let horizontal_change = key_change(VirtualKeyCode::PageUp, VirtualKeyCode::PageDown);
+    let vertical_change = key_change(VirtualKeyCode::Home, VirtualKeyCode::End);
+    if horizontal_change == 0 && vertical_change == 0 {
+        return;
+    }
+
+    let graphics_settings = &graphics.settings.graphics_settings;
+    let render_distance = (graphics_settings.render_distance as i32 + horizontal_change) as u8;
+    let vertical_render_distance =
+        (graphics_settings.vertical_render_distance as i32 + vertical_change) as u8;
+    graphics.set_render_distance(game_state, render_distance, vertical_render_distance);
+    println!(
+        "render distance: {} horizontal, {} vertical",
+        graphics.settings.graphics_settings.render_distance,
+        graphics.settings.graphics_settings.vertical_render_distance
+    );
+}
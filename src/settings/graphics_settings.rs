@@ -1,15 +1,261 @@
+use nalgebra::Vector3;
+
 pub struct GraphicsSettings {
     pub field_of_view: f32,
+    /// loaded chunks around the player's chunk along x and z
     pub render_distance: u8,
+    /// loaded chunks above and below the player's chunk
+    pub vertical_render_distance: u8,
+    /// edge length of a chunk in blocks, must be a power of two of at least 16
+    pub chunk_size: u32,
+    /// levels of detail including the full resolution chunks, every further level halves the resolution
+    /// and covers twice the area of the previous one
     pub level_of_detail_layers: u8,
+    pub normal_mapping: bool,
+    /// depth of the height maps in blocks, 0 disables parallax mapping
+    pub parallax_depth: f32,
+    /// lets rays skip bricks without blocks instead of only relying on the distance field
+    pub brick_skipping: bool,
+    pub distance_field_generator: DistanceFieldGenerator,
+    /// fixed direction towards the sun instead of the one of the time of day, does not need to be normalized
+    pub sun_direction: Option<Vector3<f32>>,
+    pub shadows: bool,
+    /// radius of the sun disk in degrees, larger values give softer shadows
+    pub sun_angular_radius: f32,
+    pub ambient_occlusion: AmbientOcclusion,
+    /// traces random diffuse bounces and averages the frames while the camera and terrain stay the same
+    pub accumulation: bool,
+    /// diffuse bounces per path while accumulating
+    pub path_bounces: u32,
+    /// outlines the block under the crosshair
+    pub block_outline: bool,
+    /// shows a translucent block where the next block would be placed
+    pub placement_preview: bool,
+    /// tints the sky colour distant terrain fades into
+    pub fog_color: Vector3<f32>,
+    /// fog per block at height 0, 0 only fades out the edge of the loaded area
+    pub fog_density: f32,
+    /// how quickly the fog thins out with height
+    pub fog_height_falloff: f32,
+    pub underwater_fog_color: Vector3<f32>,
+    pub underwater_fog_density: f32,
+    pub tonemapper: Tonemapper,
+    /// brightness adjustment in stops, applied on top of the auto exposure
+    pub exposure: f32,
+    /// adapts the exposure to the average brightness of the image
+    pub auto_exposure: bool,
+    /// passes applied to the rendered image in this order, afterwards it is encoded as sRGB
+    pub post_processing: Vec<PostProcessPass>,
+    /// luminance above which pixels bloom
+    pub bloom_threshold: f32,
+    pub bloom_intensity: f32,
+    pub sharpening_strength: f32,
+    /// darkening in the corners of the image
+    pub vignette_strength: f32,
+    pub color_grading: ColorGrading,
+    /// fraction of the window resolution the rays are traced at, the image is upscaled to the window afterwards
+    pub render_scale: f32,
+    pub upscaling: Upscaling,
+    /// host memory in bytes the stored chunks may use before chunks outside of the loaded area are evicted
+    pub chunk_memory_budget: usize,
+}
+
+/// Algorithm filling the distance fields of the chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DistanceFieldGenerator {
+    /// 16 diagonal sweeps propagating distances, fast but only approximates the Chebyshev distance
+    Sweep,
+    /// one exact 1D transform per axis
+    Chebyshev,
+}
+
+/// Quality of the ambient occlusion darkening corners and crevices, the values match `ambient_occlusion` in raytrace.glsl.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AmbientOcclusion {
+    Off = 0,
+    /// darkens the corners of faces next to other blocks
+    Low = 1,
+    /// additionally darkens faces with blocks a few blocks away using the distance field
+    High = 2,
+}
+
+/// Curve compressing the HDR colours into the displayable range, the values match `tonemapper` in tonemap.comp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tonemapper {
+    Reinhard = 0,
+    /// filmic curve with more contrast and saturated highlights
+    Aces = 1,
+}
+
+/// Reconstruction of the window sized image from the traced one, the values match `upscaling` in upscale.comp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Upscaling {
+    Bilinear = 0,
+    /// jitters the rays every frame and blends the current image with the reprojected previous ones
+    Temporal = 1,
+}
+
+/// Compute passes that can be chained after the raytracer, declared in the order they are usually applied in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PostProcessPass {
+    /// adds a blurred copy of the bright parts, expects HDR colours
+    Bloom = 0,
+    Tonemap = 1,
+    /// looks up the colours in a table built from `color_grading`, expects tonemapped colours
+    ColorGrading = 2,
+    Sharpening = 3,
+    Vignette = 4,
+}
+
+impl PostProcessPass {
+    pub const ALL: [PostProcessPass; 5] = [
+        PostProcessPass::Bloom,
+        PostProcessPass::Tonemap,
+        PostProcessPass::ColorGrading,
+        PostProcessPass::Sharpening,
+        PostProcessPass::Vignette,
+    ];
+}
+
+/// Adjustments baked into the colour grading table, applied to the sRGB encoded colours.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorGrading {
+    /// 1 leaves the contrast unchanged
+    pub contrast: f32,
+    /// 0 is greyscale, 1 leaves the colours unchanged
+    pub saturation: f32,
+    /// positive values warm the image up, negative values cool it down
+    pub temperature: f32,
+}
+
+impl ColorGrading {
+    pub const NEUTRAL: Self = Self {
+        contrast: 1.,
+        saturation: 1.,
+        temperature: 0.,
+    };
 }
 
 impl GraphicsSettings {
+    pub const MAX_RENDER_DISTANCE: u8 = 6;
+    pub const MIN_RENDER_SCALE: f32 = 0.25;
+
     pub fn standard() -> Self {
         Self {
             field_of_view: 90.,
             render_distance: 1,
+            vertical_render_distance: 1,
+            chunk_size: 64,
             level_of_detail_layers: 1,
+            normal_mapping: true,
+            parallax_depth: 0.05,
+            brick_skipping: true,
+            distance_field_generator: DistanceFieldGenerator::Chebyshev,
+            sun_direction: None,
+            shadows: true,
+            sun_angular_radius: 1.5,
+            ambient_occlusion: AmbientOcclusion::High,
+            accumulation: false,
+            path_bounces: 2,
+            block_outline: true,
+            placement_preview: false,
+            fog_color: Vector3::repeat(1.),
+            fog_density: 0.004,
+            fog_height_falloff: 0.02,
+            underwater_fog_color: Vector3::new(0.1, 0.35, 0.45),
+            underwater_fog_density: 0.15,
+            tonemapper: Tonemapper::Aces,
+            exposure: 0.,
+            auto_exposure: true,
+            post_processing: PostProcessPass::ALL.to_vec(),
+            bloom_threshold: 1.,
+            bloom_intensity: 0.3,
+            sharpening_strength: 0.2,
+            vignette_strength: 0.3,
+            color_grading: ColorGrading {
+                contrast: 1.05,
+                saturation: 1.1,
+                temperature: 0.02,
+            },
+            render_scale: 1.,
+            upscaling: Upscaling::Temporal,
+            chunk_memory_budget: 512 * 1024 * 1024,
         }
     }
+
+    /// Number of chunks around the player's chunk that are loaded along each axis
+    pub fn render_distances(&self) -> Vector3<i32> {
+        Vector3::new(
+            self.render_distance as i32,
+            self.vertical_render_distance as i32,
+            self.render_distance as i32,
+        )
+    }
+
+    /// Size of the loaded area in chunks
+    pub fn loaded_area_size(&self) -> Vector3<i32> {
+        self.render_distances() * 2 + Vector3::repeat(1)
+    }
+
+    pub fn loaded_chunk_count(&self) -> usize {
+        self.loaded_area_size().product() as usize
+    }
+
+    /// Resolution the rays are traced at for the given window size.
+    pub fn render_extent(&self, window_extent: [u32; 2]) -> [u32; 2] {
+        window_extent.map(|length| ((length as f32 * self.render_scale).ceil() as u32).clamp(1, length.max(1)))
+    }
+
+    /// Removes the pass from the post processing chain or inserts it where it is declared in `PostProcessPass`.
+    /// Returns whether the pass is enabled afterwards.
+    pub fn toggle_post_process_pass(&mut self, pass: PostProcessPass) -> bool {
+        if let Some(index) = self.post_processing.iter().position(|&enabled| enabled == pass) {
+            self.post_processing.remove(index);
+            return false;
+        }
+        let index = self
+            .post_processing
+            .iter()
+            .take_while(|&&enabled| (enabled as u8) < pass as u8)
+            .count();
+        self.post_processing.insert(index, pass);
+        true
+    }
+
+    /// Number of chunk images of all coarser levels of detail, at least one so the descriptor array isn't empty
+    pub fn lod_chunk_count(&self) -> usize {
+        (self.level_of_detail_layers.saturating_sub(1) as usize * self.loaded_chunk_count()).max(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_toggle_post_process_pass() {
+        let mut settings = GraphicsSettings::standard();
+        let standard_chain = settings.post_processing.clone();
+
+        assert!(!settings.toggle_post_process_pass(PostProcessPass::ColorGrading));
+        assert!(!settings.post_processing.contains(&PostProcessPass::ColorGrading));
+        assert!(settings.toggle_post_process_pass(PostProcessPass::ColorGrading));
+        assert_eq!(settings.post_processing, standard_chain);
+
+        settings.post_processing.clear();
+        settings.toggle_post_process_pass(PostProcessPass::Vignette);
+        settings.toggle_post_process_pass(PostProcessPass::Bloom);
+        assert_eq!(settings.post_processing, [PostProcessPass::Bloom, PostProcessPass::Vignette]);
+    }
+
+    #[test]
+    fn test_render_extent() {
+        let mut settings = GraphicsSettings::standard();
+        settings.render_scale = 1.;
+        assert_eq!(settings.render_extent([1920, 1080]), [1920, 1080]);
+        settings.render_scale = 0.5;
+        assert_eq!(settings.render_extent([3840, 2161]), [1920, 1081]);
+        settings.render_scale = GraphicsSettings::MIN_RENDER_SCALE;
+        assert_eq!(settings.render_extent([2, 0]), [1, 1]);
+    }
 }
@@ -0,0 +1,14 @@
+// Reads blocks of the loaded chunks in world coordinates.
+// Expects `block_data[loaded_chunk_count]` and the `chunk_info` buffer to be declared by the including shader.
+
+// the images of uniform chunks are never written, so their block id is taken from the chunk info
+uint read_block(ivec3 pos) {
+    uint chunk_index = chunk_storage_index(pos);
+    uint info = chunk_info[chunk_index];
+    if(is_uniform_chunk(info)) {
+        return info & BLOCK_ID_MASK;
+    }
+    ivec3 in_chunk_pos = rem_euclid_ivec3(pos, CHUNK_SIZE);
+    uint value = imageLoad(block_data[chunk_index], in_chunk_pos).x;
+    return value;
+}
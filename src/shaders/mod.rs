@@ -12,6 +12,13 @@ pub mod distance_gen {
         linalg_type: "nalgebra",
     }
 }
+pub mod distance_chebyshev {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/distance_field/distance_field_chebyshev.comp",
+        linalg_type: "nalgebra",
+    }
+}
 pub mod distance_setup {
     vulkano_shaders::shader! {
         ty: "compute",
@@ -19,6 +26,20 @@ pub mod distance_setup {
         linalg_type: "nalgebra",
     }
 }
+pub mod distance_store {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/distance_field/distance_field_store.comp",
+        linalg_type: "nalgebra",
+    }
+}
+pub mod brick_occupancy {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/distance_field/brick_occupancy.comp",
+        linalg_type: "nalgebra",
+    }
+}
 pub mod rendering {
     vulkano_shaders::shader! {
         ty: "compute",
@@ -26,3 +47,59 @@ pub mod rendering {
         linalg_type: "nalgebra",
     }
 }
+pub mod auto_exposure {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/post_processing/auto_exposure.comp",
+        linalg_type: "nalgebra",
+    }
+}
+pub mod tonemap {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/post_processing/tonemap.comp",
+        linalg_type: "nalgebra",
+    }
+}
+pub mod bloom {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/post_processing/bloom.comp",
+        linalg_type: "nalgebra",
+    }
+}
+pub mod sharpening {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/post_processing/sharpening.comp",
+        linalg_type: "nalgebra",
+    }
+}
+pub mod vignette {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/post_processing/vignette.comp",
+        linalg_type: "nalgebra",
+    }
+}
+pub mod color_grading {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/post_processing/color_grading.comp",
+        linalg_type: "nalgebra",
+    }
+}
+pub mod present {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/post_processing/present.comp",
+        linalg_type: "nalgebra",
+    }
+}
+pub mod upscale {
+    vulkano_shaders::shader! {
+        ty: "compute",
+        path: "src/shaders/post_processing/upscale.comp",
+        linalg_type: "nalgebra",
+    }
+}
@@ -0,0 +1,15 @@
+// Helpers shared by the post processing passes, which all run on 16x16 tiles.
+
+const vec3 LUMA = vec3(0.2126, 0.7152, 0.0722);
+
+float luminance(vec3 color) {
+    return dot(color, LUMA);
+}
+
+vec3 linear_to_srgb(vec3 color) {
+    return mix(color * 12.92, 1.055 * pow(color, vec3(1. / 2.4)) - 0.055, greaterThan(color, vec3(0.0031308)));
+}
+
+vec3 srgb_to_linear(vec3 color) {
+    return mix(color / 12.92, pow((color + 0.055) / 1.055, vec3(2.4)), greaterThan(color, vec3(0.04045)));
+}
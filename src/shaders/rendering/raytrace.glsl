@@ -1,4 +1,4 @@
-layout (constant_id = 0) const int render_distance = 4;
+#include "../util.glsl"
 
 layout(local_size_x = 16, local_size_y = 16, local_size_z = 1) in;
 layout(set = 0, binding = 1) writeonly buffer LookingAtBlock{
@@ -8,45 +8,93 @@ layout(set = 0, binding = 1) writeonly buffer LookingAtBlock{
 } looking_at;
 layout(set = 0, binding = 2) readonly uniform GpuGraphicsSettings{
     float fov;
+    uint normal_mapping; // 0 means the geometric face normal is used for lighting
+    float parallax_depth; // 0 disables parallax mapping
+    uint brick_skipping;
+    uint shadows; // 0 disables shadow rays
+    float sun_angular_radius; // radius of the sun disk in radians, 0 gives hard shadows
+    uint ambient_occlusion; // 0 off, 1 from the neighbouring voxels, 2 additionally from the distance field
+    uint accumulation; // 0 renders every frame on its own, otherwise diffuse bounces are traced and averaged over frames
+    uint path_bounces; // diffuse bounces per path while accumulating
+    uint block_outline; // 0 disables the outline of the block under the crosshair
+    uint placement_preview; // 0 disables the translucent block where the next block would be placed
+    float fog_density; // per block at height 0
+    float fog_height_falloff; // exponent of the decrease of the fog density per block of height
+    vec3 fog_color; // tints the sky colour distant terrain fades into
+    float underwater_fog_density;
+    vec3 underwater_fog_color;
 } settings;
-layout(r16ui, set = 0, binding = 3) readonly uniform uimage3D block_data[(render_distance * 2 + 1) * (render_distance * 2 + 1) * (render_distance * 2 + 1)];
-layout(r8ui, set = 0, binding = 4) readonly uniform uimage3D distance_data[(render_distance * 2 + 1) * (render_distance * 2 + 1) * (render_distance * 2 + 1)];
+layout(r16ui, set = 0, binding = 3) readonly uniform uimage3D block_data[loaded_chunk_count];
+layout(r8ui, set = 0, binding = 4) readonly uniform uimage3D distance_data[loaded_chunk_count];
 layout(set = 0, binding = 5) uniform texture2DArray textures;
 layout(set = 0, binding = 6) uniform sampler texture_sampler;
+layout(set = 0, binding = 7) uniform texture2DArray normal_textures; // xyz: tangent space normal, w: height
+layout(set = 0, binding = 8) readonly buffer ChunkInfo {
+    uint chunk_info[]; // UNIFORM_CHUNK_FLAG | block id for chunks of a single block type, 0 otherwise
+};
+layout(r8ui, set = 0, binding = 9) readonly uniform uimage3D brick_occupancy[loaded_chunk_count];
+layout(set = 0, binding = 10) buffer TraversalStats {
+    uint enabled; // set by the cpu while benchmarking
+    uint rays;
+    uint steps;
+} traversal_stats;
+layout(r16ui, set = 0, binding = 11) readonly uniform uimage3D lod_block_data[lod_chunk_count];
+layout(set = 0, binding = 12) readonly buffer LodChunkInfo {
+    uint lod_chunk_info[]; // like chunk_info, for the chunks of all coarser levels of detail
+};
+layout(set = 0, binding = 13) readonly buffer ChunkLights {
+    // per chunk image LIGHT_LIST_STRIDE entries, the light count followed by in-chunk positions with 10 bits per axis
+    uint chunk_lights[];
+};
+struct BlockMaterial {
+    vec4 emission; // rgb: colour, a: intensity
+    vec4 transmission; // rgb: absorption per block travelled through, a: refractive index
+};
+layout(set = 0, binding = 14) readonly buffer BlockMaterials {
+    BlockMaterial block_materials[]; // indexed by block id
+};
+layout(rgba32f, set = 0, binding = 15) uniform image2D accumulation_image; // rgb: sum of the frames, a: their count
 
 
 layout(push_constant) uniform PushConstants {
     mat4 cam_transform;
+    ivec4 target_block; // xyz: block under the crosshair in the last frame, w: 0 if there is none
+    ivec4 placement_block; // xyz: where the next block would be placed, w: 0 if there is none
+    vec3 sun_direction; // normalized, pointing towards the sun, follows the time of day
+    uint frame; // counts the drawn frames, so random samples change over time
+    vec2 jitter; // offset of the rays within their pixels for the temporal upscaling
+    uint accumulated_frames; // frames already in the accumulation image, 0 starts over
 } push;
 
-const vec3 sun_ray = normalize(vec3(0.267, 0.886, -0.16));
-
 
-#include "../util.glsl"
+#include "../chunk_access.glsl"
 
 const vec3[] debug_colors = {vec3(1., 0., 0.), vec3(0., 1., 0.), vec3(0., 0., 1.)};
 
 vec3 player_position = push.cam_transform[3].xyz;
+uint traversal_steps = 0; // summed over all rays of this invocation for TraversalStats
+// differs per pixel and frame, so the noise of the random sampling averages out
+uint rng_state = pcg_hash(gl_GlobalInvocationID.x + pcg_hash(gl_GlobalInvocationID.y + pcg_hash(push.frame)));
 
-uint read_block(ivec3 pos) {
-    uint chunks_length = render_distance * 2 + 1;
-    ivec3 storage_pos = rem_euclid_ivec3(pos, int(chunks_length) * CHUNK_SIZE);
-    ivec3 in_chunk_pos = rem_euclid_ivec3(pos, CHUNK_SIZE);
+float random() {
+    rng_state = pcg_hash(rng_state);
+    return float(rng_state) / 4294967296.;
+}
 
-    uvec3 chunk_address = uvec3(floor(storage_pos / CHUNK_SIZE));
-    uint chunk_index = uint(dot(chunk_address, ivec3(1, chunks_length, chunks_length * chunks_length)));
+vec2 random2() {
+    return vec2(random(), random());
+}
 
-    uint value = imageLoad(block_data[chunk_index], in_chunk_pos).x;
-    return value;
+bool is_brick_occupied(ivec3 pos) {
+    ivec3 in_chunk_brick = rem_euclid_ivec3(pos, CHUNK_SIZE) / BRICK_SIZE;
+    return imageLoad(brick_occupancy[chunk_storage_index(pos)], in_chunk_brick).x != 0;
 }
 uint read_distance(ivec3 pos) {
-    uint chunks_length = render_distance * 2 + 1;
-    ivec3 storage_pos = rem_euclid_ivec3(pos, int(chunks_length) * CHUNK_SIZE);
+    uint chunk_index = chunk_storage_index(pos);
+    if(is_uniform_chunk(chunk_info[chunk_index])) {
+        return 0;
+    }
     ivec3 in_chunk_pos = rem_euclid_ivec3(pos, CHUNK_SIZE);
-
-    uvec3 chunk_address = uvec3(floor(storage_pos / CHUNK_SIZE));
-    uint chunk_index = uint(dot(chunk_address, ivec3(1, chunks_length, chunks_length * chunks_length)));
-
     uint d = imageLoad(distance_data[chunk_index], in_chunk_pos).x;
     return d;
 }
@@ -54,18 +102,29 @@ uint read_distance(ivec3 pos) {
 
 bool is_inside_loaded_area(ivec3 pos) {
     ivec3 player_chunk_middle = ivec3(floor(player_position / float(CHUNK_SIZE))) * CHUNK_SIZE + CHUNK_SIZE / 2;
-    ivec3 lower_bound = player_chunk_middle - CHUNK_SIZE * render_distance - CHUNK_SIZE / 2;
-    ivec3 upper_bound = player_chunk_middle + CHUNK_SIZE * render_distance + CHUNK_SIZE / 2 - 1;
+    ivec3 lower_bound = player_chunk_middle - CHUNK_SIZE * render_distances - CHUNK_SIZE / 2;
+    ivec3 upper_bound = player_chunk_middle + CHUNK_SIZE * render_distances + CHUNK_SIZE / 2 - 1;
     return all(lessThan(lower_bound, pos)) && all(lessThan(pos, upper_bound));
 }
 
-bool single_ray_df(in vec3 ro, in vec3 rd, out uint block_id, out vec3 surface_normal, out vec3 hit_point) {
+// moves the traversal state to the last voxel the ray enters before reaching the distance t
+void advance_traversal(float t, vec3 t_start, vec3 inv_rd, inout ivec3 offset, inout vec3 t_dist_to_next) {
+    vec3 step_t = abs(inv_rd);
+    vec3 crossings = max(floor((t - t_start) / step_t) + 1., vec3(0.));
+    crossings = mix(crossings, vec3(0.), isinf(step_t));
+    offset = ivec3(crossings);
+    t_dist_to_next = t_start + crossings * step_t;
+}
+
+// blocks with the id pass_through_id are treated like air, which lets rays continue through transparent blocks
+bool single_ray_df(in vec3 ro, in vec3 rd, in uint pass_through_id, out uint block_id, out vec3 surface_normal, out vec3 hit_point) {
     const vec3 inv_rd = 1. / rd;
 
     ivec3 oct_rd01 = ivec3(greaterThan(rd, vec3(0.)));
     ivec3 oct_rd11 = (oct_rd01 * 2) - ivec3(1);
 
-    vec3 t_dist_to_next = (vec3(oct_rd01) - fract(ro)) * inv_rd;
+    const vec3 t_start = (vec3(oct_rd01) - fract(ro)) * inv_rd;
+    vec3 t_dist_to_next = t_start;
 
     ivec3 offset = ivec3(0);
     ivec3 pos = ivec3(floor(ro));
@@ -73,6 +132,7 @@ bool single_ray_df(in vec3 ro, in vec3 rd, out uint block_id, out vec3 surface_n
     ivec3 last_read_pos = pos;
 
     while(is_inside_loaded_area(pos)) {
+        traversal_steps++;
         int next_xyz = argmin(t_dist_to_next);
         float old_distance = t_dist_to_next[next_xyz];
         t_dist_to_next[next_xyz] += abs(inv_rd[next_xyz]);
@@ -83,9 +143,30 @@ bool single_ray_df(in vec3 ro, in vec3 rd, out uint block_id, out vec3 surface_n
 
         if (chebyshev_length(last_read_pos - pos) >= free_dist){
             last_read_pos = pos;
-            free_dist = read_distance(pos);
-            uint block_type = read_block(pos);
-            if(block_type > 0) {
+            uint info = chunk_info[chunk_storage_index(pos)];
+            uint block_type;
+            if(is_uniform_chunk(info)) {
+                block_type = info & BLOCK_ID_MASK;
+                free_dist = 0;
+                if(block_type == pass_through_id) { // skip the whole chunk in one step
+                    ivec3 chunk_pos = ivec3(floor(vec3(pos) / CHUNK_SIZE));
+                    float t_exit = chunk_AABB_test(chunk_pos, ro, rd).y;
+                    advance_traversal(t_exit - 0.0001, t_start, inv_rd, offset, t_dist_to_next);
+                    continue;
+                }
+            }
+            else if(settings.brick_skipping != 0 && pass_through_id == 0 && !is_brick_occupied(pos)) {
+                free_dist = 0;
+                ivec3 brick_pos = ivec3(floor(vec3(pos) / BRICK_SIZE));
+                float t_exit = brick_AABB_test(brick_pos, ro, rd).y;
+                advance_traversal(t_exit - 0.0001, t_start, inv_rd, offset, t_dist_to_next);
+                continue;
+            }
+            else {
+                free_dist = read_distance(pos);
+                block_type = read_block(pos);
+            }
+            if(block_type != pass_through_id) {
                 block_id = block_type;
                 vec3 normal = vec3(0);
                 normal[next_xyz] = -oct_rd11[next_xyz];
@@ -99,6 +180,98 @@ bool single_ray_df(in vec3 ro, in vec3 rd, out uint block_id, out vec3 surface_n
     return false;
 }
 
+// center of the area covered by a level of detail in chunks of that level, level 0 being the full resolution chunks
+ivec3 lod_center(int level) {
+    return chunk_of(ivec3(floor(player_position)) >> level);
+}
+
+// see lod_chunk_buffer_index, pos is given in voxels of the level
+uint lod_storage_index(int level, ivec3 pos) {
+    return (level - 1) * loaded_chunk_count + chunk_storage_index(pos);
+}
+
+// ray parameter at which the ray leaves the area covered by a level of detail
+float lod_area_exit(int level, vec3 ro, vec3 rd) {
+    float scale = float(1 << level);
+    ivec3 center = lod_center(level);
+    vec3 area_min = vec3((center - render_distances) * CHUNK_SIZE) * scale;
+    vec3 area_max = vec3((center + render_distances + 1) * CHUNK_SIZE) * scale;
+    return AABB_test(area_min, area_max, ro, rd).y;
+}
+
+// traverses the voxels of a coarser level of detail, starting where the ray leaves the previous level
+bool single_ray_lod(int level, vec3 ro, vec3 rd, uint pass_through_id, out uint block_id, out vec3 surface_normal, out vec3 hit_point) {
+    const float scale = float(1 << level);
+    const ivec3 center = lod_center(level);
+    ro = (ro + rd * max(lod_area_exit(level - 1, ro, rd), 0.)) / scale; // in voxels of this level
+
+    const vec3 inv_rd = 1. / rd;
+
+    ivec3 oct_rd01 = ivec3(greaterThan(rd, vec3(0.)));
+    ivec3 oct_rd11 = (oct_rd01 * 2) - ivec3(1);
+
+    const vec3 t_start = (vec3(oct_rd01) - fract(ro)) * inv_rd;
+    vec3 t_dist_to_next = t_start;
+
+    ivec3 offset = ivec3(0);
+    ivec3 pos = ivec3(floor(ro));
+
+    while(is_in_loaded_area(pos, center)) {
+        traversal_steps++;
+        int next_xyz = argmin(t_dist_to_next);
+        float old_distance = t_dist_to_next[next_xyz];
+        t_dist_to_next[next_xyz] += abs(inv_rd[next_xyz]);
+        offset[next_xyz] += 1;
+
+        pos = ivec3(floor(ro)) + offset * oct_rd11;
+        if(!is_in_loaded_area(pos, center)) {
+            break;
+        }
+
+        uint chunk_index = lod_storage_index(level, pos);
+        uint info = lod_chunk_info[chunk_index];
+        uint block_type;
+        if(is_uniform_chunk(info)) {
+            block_type = info & BLOCK_ID_MASK;
+            if(block_type == pass_through_id) { // skip the whole chunk in one step
+                float t_exit = chunk_AABB_test(chunk_of(pos), ro, rd).y;
+                advance_traversal(t_exit - 0.0001, t_start, inv_rd, offset, t_dist_to_next);
+                continue;
+            }
+        }
+        else {
+            block_type = imageLoad(lod_block_data[chunk_index], rem_euclid_ivec3(pos, CHUNK_SIZE)).x;
+        }
+        if(block_type != pass_through_id) {
+            block_id = block_type;
+            vec3 normal = vec3(0);
+            normal[next_xyz] = -oct_rd11[next_xyz];
+            surface_normal = normal;
+            hit_point = (ro + rd * old_distance) * scale;
+            return true;
+        }
+    }
+
+    return false;
+}
+
+// traces the full resolution chunks and falls back to the coarser levels of detail once the ray leaves them.
+// The ray travels through blocks of pass_through_id, the transparent block it is inside of or 0 for air,
+// and stops at the first other block. Leaving a transparent block into air is reported as a hit of block 0.
+bool single_ray(vec3 ro, vec3 rd, uint pass_through_id, out uint block_id, out vec3 surface_normal, out vec3 hit_point, out int hit_level) {
+    hit_level = 0;
+    if(single_ray_df(ro, rd, pass_through_id, block_id, surface_normal, hit_point)) {
+        return true;
+    }
+    for(int level = 1; level < level_of_detail_layers; level++) {
+        if(single_ray_lod(level, ro, rd, pass_through_id, block_id, surface_normal, hit_point)) {
+            hit_level = level;
+            return true;
+        }
+    }
+    return false;
+}
+
 bool single_ray_basic(in vec3 ro, in vec3 rd, out uint block_id, out vec3 surface_normal, out vec3 hit_point) {
     const vec3 inv_rd = 1. / rd;
 
@@ -132,7 +305,8 @@ bool single_ray_basic(in vec3 ro, in vec3 rd, out uint block_id, out vec3 surfac
     return false;
 }
 
-vec4 get_texture(uint block_id, vec3 hit_normal, vec3 position) {
+// uv coordinates of the hit point on the hit face and the texture layer of that face
+vec3 face_uv(uint block_id, vec3 hit_normal, vec3 position) {
     vec2 uv;
     uint side = 1;
     if(hit_normal.y == 0.) {
@@ -142,89 +316,518 @@ vec4 get_texture(uint block_id, vec3 hit_normal, vec3 position) {
         uv = position.xz;
         side = hit_normal.y > 0. ? 0 : 2;
     }
+    return vec3(fract(-uv), 3 * (block_id - 1) + side);
+}
 
-    return texture(sampler2DArray(textures, texture_sampler), vec3(fract(-uv), 3 * (block_id - 1) + side));
+// world space directions in which the u and v texture coordinates grow on the face with the given normal
+mat3 face_tbn(vec3 hit_normal) {
+    vec3 tangent = abs(hit_normal.x) == 1. ? vec3(0., 0., -1.) : vec3(-1., 0., 0.);
+    vec3 bitangent = hit_normal.y == 0. ? vec3(0., -1., 0.) : vec3(0., 0., -1.);
+    return mat3(tangent, bitangent, hit_normal);
 }
 
-vec3 raycast() {
-    const vec2 render_img_size = imageSize(render_target).xy;
-    if (gl_GlobalInvocationID.x == render_img_size.x / 2 && gl_GlobalInvocationID.y == render_img_size.y / 2) {
-        looking_at.block_id = 0;
+// shifts the uv coordinates along the view direction according to the height map
+vec3 parallax_uv(vec3 uv_layer, mat3 tbn, vec3 rd) {
+    if(settings.parallax_depth <= 0.) {
+        return uv_layer;
     }
-    const vec2 norm_coordinates = vec2(((gl_GlobalInvocationID.xy) / render_img_size.x) - vec2(0.5, render_img_size.y / render_img_size.x * 0.5));
-    vec3 rd = normalize((vec4(norm_coordinates * tan(settings.fov / 2.), 1., 1.) * push.cam_transform).xyz);
-    vec3 ro = player_position;
+    vec3 view_tangent = -rd * tbn;
+    float height = texture(sampler2DArray(normal_textures, texture_sampler), uv_layer).a;
+    vec2 offset = view_tangent.xy / max(view_tangent.z, 0.1) * (1. - height) * settings.parallax_depth;
+    return vec3(fract(uv_layer.xy - offset), uv_layer.z);
+}
+
+vec4 get_texture(vec3 uv_layer) {
+    return texture(sampler2DArray(textures, texture_sampler), uv_layer);
+}
+
+vec3 get_normal(vec3 uv_layer, mat3 tbn) {
+    if(settings.normal_mapping == 0) {
+        return tbn[2];
+    }
+    vec3 tangent_normal = texture(sampler2DArray(normal_textures, texture_sampler), uv_layer).xyz * 2. - 1.;
+    return normalize(tbn * tangent_normal);
+}
+
+// the moon is opposite to the sun and takes over once the sun has set
+vec3 light_direction() {
+    return push.sun_direction.y >= 0. ? push.sun_direction : -push.sun_direction;
+}
+
+// colour of the sun or moon light reaching the ground, fading out towards the horizon and reddening at sunrise and sunset
+vec3 direct_light_color() {
+    float height = push.sun_direction.y;
+    if(height < 0.) {
+        return vec3(0.1, 0.12, 0.18) * smoothstep(0., 0.1, -height);
+    }
+    vec3 sunset = vec3(1., 0.5, 0.25);
+    return mix(sunset, vec3(1., 0.97, 0.9), smoothstep(0., 0.3, height)) * smoothstep(0., 0.05, height);
+}
+
+// sky colour without the sun and moon disks, blending between a night and a day gradient by the height of the sun
+vec3 sky_gradient(vec3 rd) {
+    float day = smoothstep(-0.1, 0.15, push.sun_direction.y);
+    vec3 zenith = mix(vec3(0.005, 0.008, 0.02), vec3(0.25, 0.45, 0.85), day);
+    vec3 horizon = mix(vec3(0.02, 0.03, 0.06), vec3(0.7, 0.8, 0.95), day);
+    vec3 color = mix(horizon, zenith, pow(clamp(rd.y, 0., 1.), 0.5));
+
+    // orange glow around the sun while it is close to the horizon
+    float sunset = 1. - smoothstep(0., 0.3, abs(push.sun_direction.y));
+    float towards_sun = max(dot(rd, push.sun_direction), 0.);
+    color += vec3(0.9, 0.35, 0.1) * sunset * pow(towards_sun, 4.) * (1. - clamp(rd.y, 0., 1.));
+
+    // below the horizon the sky fades to a dark ground colour
+    return mix(color * 0.3, color, smoothstep(-0.2, 0., rd.y));
+}
+
+// colour seen by rays that leave the loaded area
+vec3 sky_color(vec3 rd) {
+    vec3 color = sky_gradient(rd);
+    float sun_cos = dot(rd, push.sun_direction);
+    float disk_cos = cos(max(settings.sun_angular_radius, 0.005));
+    color += direct_light_color() * (smoothstep(disk_cos - 0.0005, disk_cos, sun_cos) * 20. + pow(max(sun_cos, 0.), 300.) * 0.5)
+        * step(0., push.sun_direction.y);
+    color += vec3(0.8, 0.85, 1.) * smoothstep(disk_cos - 0.0005, disk_cos, -sun_cos) * step(push.sun_direction.y, 0.);
+    return color;
+}
+
+// light scattered by the sky onto a surface, approximated by the sky gradient above and to the side of it
+vec3 ambient_light(vec3 normal) {
+    vec3 up = sky_gradient(vec3(0., 1., 0.));
+    vec3 side = sky_gradient(normalize(vec3(normal.x, 0.05, normal.z)));
+    return mix(side, up, normal.y * 0.5 + 0.5) * 0.6;
+}
+
+// direction towards a random point on the sun or moon disk, varying per pixel and frame for soft shadows
+vec3 jittered_light_direction() {
+    vec3 light = light_direction();
+    vec3 tangent = normalize(cross(light, abs(light.y) < 0.99 ? vec3(0., 1., 0.) : vec3(1., 0., 0.)));
+    vec3 bitangent = cross(light, tangent);
+    vec2 random = random2();
+    float radius = sqrt(random.x) * tan(settings.sun_angular_radius);
+    float angle = random.y * 2. * PI;
+    return normalize(light + (tangent * cos(angle) + bitangent * sin(angle)) * radius);
+}
+
+// 1 if the sun or moon can be seen from the point, 0 if an opaque block is in the way. Transparent blocks let the light through.
+float sun_visibility(vec3 point, vec3 surface_normal) {
+    if(settings.shadows == 0 || dot(surface_normal, light_direction()) <= 0.) {
+        return 1.;
+    }
+    vec3 rd = jittered_light_direction();
+    vec3 ro = point + surface_normal * 0.001;
 
     uint block_id;
-    vec3 surface_normal;
+    vec3 hit_normal;
     vec3 hit_point;
+    int hit_level;
+    uint medium_id = 0;
+    for(int i = 0; i < 4; i++) {
+        if(!single_ray(ro, rd, medium_id, block_id, hit_normal, hit_point, hit_level)) {
+            return 1.;
+        }
+        if(block_id != 0 && !is_transparent(block_id)) {
+            return 0.;
+        }
+        medium_id = block_id;
+        ro = hit_point + rd * 0.0001;
+    }
+    return 1.;
+}
 
-    float rest_multiplier = 1.;
-    vec3 color = vec3(0);
-    for(int i = 0; i < 5 && single_ray_df(ro, rd, block_id, surface_normal, hit_point); i++) {
-        if(i == 0) {
-            if(gl_GlobalInvocationID.x == render_img_size.x / 2 && gl_GlobalInvocationID.y == render_img_size.y / 2) {
-                looking_at.hit_point = hit_point;
-                looking_at.hit_normal = surface_normal;
-                looking_at.block_id = block_id;
+bool is_occluder(ivec3 pos) {
+    uint block_id = read_block(pos);
+    return is_inside_loaded_area(pos) && block_id != 0 && !is_transparent(block_id);
+}
+
+// classic voxel ambient occlusion: every corner of the face is darkened by the blocks next to it,
+// the corner values are then interpolated over the face
+float voxel_ambient_occlusion(vec3 hit_point, vec3 surface_normal) {
+    ivec3 normal = ivec3(surface_normal);
+    ivec3 in_front = ivec3(floor(hit_point + surface_normal * 0.5));
+    ivec3 tangent = abs(normal.x) == 1 ? ivec3(0, 1, 0) : ivec3(1, 0, 0);
+    ivec3 bitangent = abs(normal.z) == 1 ? ivec3(0, 1, 0) : ivec3(0, 0, 1);
+    vec2 uv = vec2(dot(fract(hit_point), vec3(tangent)), dot(fract(hit_point), vec3(bitangent)));
+
+    bool side_u[2] = {is_occluder(in_front - tangent), is_occluder(in_front + tangent)};
+    bool side_v[2] = {is_occluder(in_front - bitangent), is_occluder(in_front + bitangent)};
+    float corners[4];
+    for(int i = 0; i < 4; i++) {
+        int u = i & 1;
+        int v = i >> 1;
+        bool corner = is_occluder(in_front + (2 * u - 1) * tangent + (2 * v - 1) * bitangent);
+        corners[i] = side_u[u] && side_v[v] ? 0. : 1. - (float(side_u[u]) + float(side_v[v]) + float(corner)) / 3.;
+    }
+    float ao = mix(mix(corners[0], corners[1], uv.x), mix(corners[2], corners[3], uv.x), uv.y);
+    return 0.25 + 0.75 * ao;
+}
+
+// larger scale occlusion: the distance field along the normal is compared to the distance from the surface,
+// smaller distances mean that other blocks are close by
+float distance_field_ambient_occlusion(vec3 hit_point, vec3 surface_normal) {
+    ivec3 in_front = ivec3(floor(hit_point + surface_normal * 0.5));
+    float occlusion = 0.;
+    float weight = 0.5;
+    for(int k = 1; k <= 4; k++) {
+        ivec3 pos = in_front + ivec3(surface_normal) * (k - 1);
+        if(!is_inside_loaded_area(pos)) {
+            break;
+        }
+        // uniform chunks have no distance field, open ones count as far from everything
+        uint d = is_uniform_chunk(chunk_info[chunk_storage_index(pos)])
+            ? (read_block(pos) == 0 ? uint(DISTANCE_FIELD_PADDING) : 0u)
+            : read_distance(pos);
+        d = min(d, uint(k));
+        occlusion += weight * float(k - int(d)) / float(k);
+        weight *= 0.5;
+    }
+    return 1. - occlusion;
+}
+
+// only the full resolution blocks are occluded, coarser levels of detail are too far away for it to be visible
+float ambient_occlusion(vec3 hit_point, vec3 surface_normal, int hit_level) {
+    if(settings.ambient_occlusion == 0 || hit_level != 0) {
+        return 1.;
+    }
+    float ao = voxel_ambient_occlusion(hit_point, surface_normal);
+    if(settings.ambient_occlusion >= 2) {
+        ao *= distance_field_ambient_occlusion(hit_point, surface_normal);
+    }
+    return ao;
+}
+
+const uint LIGHT_LIST_STRIDE = 64; // chunk_lights::LIGHT_LIST_STRIDE
+const float LIGHT_RANGE = 16.; // distance in blocks at which the light of emissive blocks has faded out
+
+vec3 emission(uint block_id) {
+    return block_materials[block_id].emission.rgb * block_materials[block_id].emission.a;
+}
+
+// inverse square falloff, windowed so it reaches 0 at LIGHT_RANGE
+float light_falloff(float distance_squared) {
+    float window = max(1. - distance_squared / (LIGHT_RANGE * LIGHT_RANGE), 0.);
+    return window * window / (distance_squared + 1.);
+}
+
+// whether the emissive block can be seen from the point, transparent blocks let the light through
+bool is_light_visible(vec3 point, vec3 surface_normal, ivec3 light) {
+    vec3 ro = point + surface_normal * 0.001;
+    vec3 rd = normalize(vec3(light) + 0.5 - ro);
+
+    uint block_id;
+    vec3 hit_normal;
+    vec3 hit_point;
+    int hit_level;
+    uint medium_id = 0;
+    for(int i = 0; i < 4; i++) {
+        if(!single_ray(ro, rd, medium_id, block_id, hit_normal, hit_point, hit_level)) {
+            return true;
+        }
+        if(block_id != 0 && !is_transparent(block_id)) {
+            return ivec3(floor(hit_point - hit_normal * 0.5)) == light;
+        }
+        medium_id = block_id;
+        ro = hit_point + rd * 0.0001;
+    }
+    return true;
+}
+
+// light of the emissive blocks in the chunks around the point. The lights in range are weighted by their unshadowed
+// contribution and a single shadow ray is cast towards one of them, picked proportionally to that weight.
+// Without shadows the sum is returned directly.
+vec3 point_light(vec3 point, vec3 surface_normal, vec3 shading_normal) {
+    ivec3 first_chunk = chunk_of(ivec3(floor(point - LIGHT_RANGE)));
+    ivec3 last_chunk = chunk_of(ivec3(floor(point + LIGHT_RANGE)));
+
+    vec3 total = vec3(0.);
+    float total_weight = 0.;
+    ivec3 picked_light;
+    vec3 picked_contribution;
+    float picked_weight = 0.;
+    for(int x = first_chunk.x; x <= last_chunk.x; x++) {
+        for(int y = first_chunk.y; y <= last_chunk.y; y++) {
+            for(int z = first_chunk.z; z <= last_chunk.z; z++) {
+                ivec3 chunk_origin = ivec3(x, y, z) * CHUNK_SIZE;
+                if(!is_inside_loaded_area(chunk_origin + CHUNK_SIZE / 2)) {
+                    continue;
+                }
+                // most of the chunks around the point are too far away for any of their lights to reach it
+                vec3 to_chunk = clamp(point, vec3(chunk_origin), vec3(chunk_origin + CHUNK_SIZE)) - point;
+                if(dot(to_chunk, to_chunk) > LIGHT_RANGE * LIGHT_RANGE) {
+                    continue;
+                }
+                uint list = chunk_storage_index(chunk_origin) * LIGHT_LIST_STRIDE;
+                uint count = min(chunk_lights[list], LIGHT_LIST_STRIDE - 1);
+                for(uint i = 1; i <= count; i++) {
+                    uint packed = chunk_lights[list + i];
+                    ivec3 light = chunk_origin + ivec3(packed & 1023u, (packed >> 10) & 1023u, (packed >> 20) & 1023u);
+                    vec3 to_light = vec3(light) + 0.5 - point;
+                    float distance_squared = dot(to_light, to_light);
+                    float cos_angle = dot(shading_normal, to_light) * inversesqrt(distance_squared);
+                    if(distance_squared > LIGHT_RANGE * LIGHT_RANGE || cos_angle <= 0.) {
+                        continue;
+                    }
+                    vec3 contribution = emission(read_block(light)) * cos_angle * light_falloff(distance_squared);
+                    float weight = dot(contribution, vec3(0.2126, 0.7152, 0.0722));
+                    total += contribution;
+                    total_weight += weight;
+                    // weighted reservoir sampling, keeps each light with a probability proportional to its weight
+                    if(random() * total_weight < weight) {
+                        picked_light = light;
+                        picked_contribution = contribution;
+                        picked_weight = weight;
+                    }
+                }
             }
         }
+    }
+    if(picked_weight <= 0. || settings.shadows == 0) {
+        return total;
+    }
+    return is_light_visible(point, surface_normal, picked_light) ? picked_contribution * total_weight / picked_weight : vec3(0.);
+}
 
+vec3 cosine_weighted_direction(vec3 normal) {
+    vec2 random = random2();
+    vec3 tangent = normalize(cross(normal, abs(normal.y) < 0.99 ? vec3(0., 1., 0.) : vec3(1., 0., 0.)));
+    vec3 bitangent = cross(normal, tangent);
+    float radius = sqrt(random.y);
+    float angle = random.x * 2. * PI;
+    return normalize((tangent * cos(angle) + bitangent * sin(angle)) * radius + normal * sqrt(1. - random.y));
+}
 
-        ro = hit_point - rd * 0.0001;
-        rd = rd - 2 * dot(rd, surface_normal) * surface_normal;
+// light reaching the point after bouncing off other blocks, estimated with one random path.
+// Directions are sampled proportionally to the cosine, so every bounce only multiplies by the surface colour.
+vec3 indirect_light(vec3 point, vec3 surface_normal) {
+    vec3 radiance = vec3(0.);
+    vec3 throughput = vec3(1.);
+    vec3 ro = point + surface_normal * 0.001;
+    vec3 rd = cosine_weighted_direction(surface_normal);
 
-        vec4 tex = get_texture(block_id, surface_normal, hit_point);
+    uint block_id;
+    vec3 hit_normal;
+    vec3 hit_point;
+    int hit_level;
+    uint medium_id = 0;
+    uint bounce = 0;
+    for(int i = 0; i < 8 && bounce < settings.path_bounces; i++) {
+        if(!single_ray(ro, rd, medium_id, block_id, hit_normal, hit_point, hit_level)) {
+            // the sun is sampled directly at every bounce, so only the sky without its disk is added here
+            radiance += throughput * sky_gradient(rd);
+            break;
+        }
+        if(block_id == 0) {
+            medium_id = 0;
+            ro = hit_point + rd * 0.0001;
+            continue;
+        }
+        vec4 tex = get_texture(face_uv(block_id, hit_normal, hit_point));
+        if(is_transparent(block_id)) {
+            throughput *= mix(vec3(1.), tex.rgb, tex.a);
+            medium_id = block_id;
+            ro = hit_point + rd * 0.0001;
+            continue;
+        }
+        vec3 lights = hit_level == 0 ? point_light(hit_point, hit_normal, hit_normal) : vec3(0.);
+        radiance += throughput * tex.rgb * (direct_light_color() * max(dot(hit_normal, light_direction()), 0.) * sun_visibility(hit_point, hit_normal) + lights);
+        // tinted by the texture like at the primary hit
+        if(is_emissive(block_id)) {
+            radiance += throughput * tex.rgb * emission(block_id);
+        }
+        throughput *= tex.rgb;
+        bounce++;
+        medium_id = 0;
+        ro = hit_point + hit_normal * 0.001;
+        rd = cosine_weighted_direction(hit_normal);
+    }
+    return radiance;
+}
 
-        color += rest_multiplier * tex.rgb;
-        rest_multiplier *= 1. - tex.a;
+// the ambient light is either approximated from the sky or traced while accumulating
+vec3 diffuse_light(vec3 normal, float sun_visibility, vec3 ambient, vec3 point_light) {
+    return ambient + direct_light_color() * max(dot(normal, light_direction()), 0.) * sun_visibility + point_light;
+}
+
+void record_traversal_stats() {
+    if(traversal_stats.enabled != 0 && all(lessThan(gl_GlobalInvocationID.xy, imageSize(render_target)))) {
+        atomicAdd(traversal_stats.rays, 1);
+        atomicAdd(traversal_stats.steps, traversal_steps);
     }
-    return color;
 }
 
-/*
-vec3 raycast() {
+float refractive_index(uint block_id) {
+    return block_materials[block_id].transmission.a;
+}
+
+// light lost travelling the distance through a transparent block, following Beer's law
+vec3 transmittance(uint medium_id, float distance) {
+    return exp(-block_materials[medium_id].transmission.rgb * distance);
+}
+
+// Schlick's approximation of the fraction of light reflected at the interface between two media
+float fresnel(float cos_incident, float n1, float n2) {
+    float r0 = (n1 - n2) / (n1 + n2);
+    r0 *= r0;
+    return r0 + (1. - r0) * pow(1. - cos_incident, 5.);
+}
+
+// horizontal distance from the camera up to which the coarsest level of detail is loaded in every direction
+float loaded_extent() {
+    return float(render_distances.x * CHUNK_SIZE << max(level_of_detail_layers - 1, 0));
+}
+
+// fog along the ray whose density decreases exponentially with height, integrated analytically.
+// Independent of the density, terrain fades into the sky before reaching the edge of the loaded area.
+float height_fog(vec3 ro, vec3 rd, float hit_distance) {
+    float height_change = settings.fog_height_falloff * rd.y * hit_distance;
+    float integral = abs(height_change) > 0.0001 ? (1. - exp(-height_change)) / height_change : 1.;
+    float optical_depth = settings.fog_density * exp(-settings.fog_height_falloff * ro.y) * hit_distance * integral;
+    vec3 offset = rd * hit_distance;
+    float horizontal_distance = max(abs(offset.x), abs(offset.z));
+    return max(1. - exp(-optical_depth), smoothstep(0.7, 1., horizontal_distance / loaded_extent()));
+}
+
+// blends the color toward the sky with distance, or toward the water colour when the camera is under water
+vec3 apply_fog(vec3 color, vec3 ro, vec3 rd, float hit_distance, bool underwater) {
+    if(underwater) {
+        float fog = 1. - exp(-settings.underwater_fog_density * hit_distance);
+        return mix(color, settings.underwater_fog_color * ambient_light(vec3(0., 1., 0.)), fog);
+    }
+    if(hit_distance >= 1e30) { // the sky itself is not fogged
+        return color;
+    }
+    return mix(color, settings.fog_color * sky_gradient(rd), height_fog(ro, rd, hit_distance));
+}
+
+const vec3 OUTLINE_COLOR = vec3(0.02);
+const vec3 PLACEMENT_PREVIEW_COLOR = vec3(0.8, 0.9, 1.);
+
+// distance of a point on the surface of the voxel to the closest edge of the voxel
+float voxel_edge_distance(vec3 point, ivec3 voxel) {
+    vec3 in_voxel = clamp(point - vec3(voxel), 0., 1.);
+    vec3 to_side = min(in_voxel, 1. - in_voxel);
+    // the smallest distance is to the face the point lies on, the middle one to the closest edge of that face
+    return to_side.x + to_side.y + to_side.z - min(to_side.x, min(to_side.y, to_side.z)) - max(to_side.x, max(to_side.y, to_side.z));
+}
+
+// outlines grow with the distance so they keep about the same width on screen
+bool is_on_outline(vec3 point, ivec3 voxel) {
+    return voxel_edge_distance(point, voxel) < max(0.01, 0.002 * distance(point, player_position));
+}
+
+// blends a translucent block with outlined edges over the color if the ray reaches the placement position first
+vec3 apply_placement_preview(vec3 color, vec3 ro, vec3 rd, float hit_distance) {
+    if(settings.placement_preview == 0 || push.placement_block.w == 0) {
+        return color;
+    }
+    ivec3 voxel = push.placement_block.xyz;
+    vec2 t = AABB_test(vec3(voxel), vec3(voxel + 1), ro, rd);
+    if(t.x > t.y || t.x < 0. || t.x > hit_distance) {
+        return color;
+    }
+    if(is_on_outline(ro + rd * t.x, voxel)) {
+        return OUTLINE_COLOR;
+    }
+    return mix(color, PLACEMENT_PREVIEW_COLOR, 0.3);
+}
+
+// returns the colour and the distance to the first hit along the camera ray
+vec4 raycast() {
     const vec2 render_img_size = imageSize(render_target).xy;
-    if(gl_GlobalInvocationID.x == render_img_size.x / 2 && gl_GlobalInvocationID.y == render_img_size.y / 2) {
+    const bool crosshair_ray = gl_GlobalInvocationID.x == render_img_size.x / 2 && gl_GlobalInvocationID.y == render_img_size.y / 2;
+    if (crosshair_ray) {
         looking_at.block_id = 0;
     }
-    const vec2 norm_coordinates = vec2(((gl_GlobalInvocationID.xy) / render_img_size.x) - vec2(0.5, render_img_size.y / render_img_size.x * 0.5));
-    const vec3 rd = normalize((vec4(norm_coordinates * tan(settings.fov / 2.), 1., 1.) * push.cam_transform).xyz);
-    const vec3 ro = player_position;
+    // while accumulating the rays are spread over the pixel for anti-aliasing and push.jitter is 0,
+    // the ray under the crosshair stays in the center since it decides which block is looked at
+    vec2 offset = settings.accumulation != 0 ? random2() - 0.5 : push.jitter;
+    vec2 pixel = vec2(gl_GlobalInvocationID.xy) + (crosshair_ray ? vec2(0.) : offset);
+    const vec2 norm_coordinates = vec2((pixel / render_img_size.x) - vec2(0.5, render_img_size.y / render_img_size.x * 0.5));
+    vec3 rd = normalize((vec4(norm_coordinates * tan(settings.fov / 2.), 1., 1.) * push.cam_transform).xyz);
+    vec3 ro = player_position;
+    const vec3 camera_rd = rd;
+    float hit_distance = 1e30; // of the first hit, for the placement preview
 
     uint block_id;
     vec3 surface_normal;
     vec3 hit_point;
+    int hit_level;
 
-    if(single_ray_df(ro, rd, block_id, surface_normal, hit_point)) {
-
-
-        vec2 uv = vec2(0);
-        uint side = 1;
-        if(surface_normal.y == -1.) {
-            uv = hit_point.xz;
-            side = 2;
+    vec3 throughput = vec3(1.);
+    vec3 color = vec3(0);
+    ivec3 camera_block = ivec3(floor(ro));
+    uint camera_block_id = is_inside_loaded_area(camera_block) ? read_block(camera_block) : 0;
+    uint medium_id = is_transparent(camera_block_id) ? camera_block_id : 0; // transparent block the ray is currently travelling through
+    for(int i = 0; i < 12; i++) {
+        if(!single_ray(ro, rd, medium_id, block_id, surface_normal, hit_point, hit_level)) {
+            color += throughput * sky_color(rd);
+            break;
         }
-        else if(surface_normal.y == 1.) {
-            uv = hit_point.xz;
-            side = 0;
+        if(i == 0 && hit_level == 0) { // blocks of coarser levels can't be edited
+            if(crosshair_ray) {
+                looking_at.hit_point = hit_point;
+                looking_at.hit_normal = surface_normal;
+                looking_at.block_id = block_id;
+            }
         }
-        else if (abs(surface_normal.x) == 1.) {
-            uv = -hit_point.zy;
+        if(i == 0) {
+            hit_distance = distance(ro, hit_point);
+            ivec3 hit_block = ivec3(floor(hit_point - surface_normal * 0.5));
+            if(settings.block_outline != 0 && hit_level == 0 && push.target_block.w != 0 && hit_block == push.target_block.xyz
+                && is_on_outline(hit_point, hit_block)) {
+                color = OUTLINE_COLOR;
+                break;
+            }
         }
-        else if (abs(surface_normal.z) == 1.) {
-            uv = -hit_point.xy;
+        if(medium_id != 0) {
+            throughput *= transmittance(medium_id, distance(ro, hit_point));
         }
-        vec4 color = texture(sampler2DArray(textures, texture_sampler), vec3(fract(uv), 3 * (block_id - 1) + side));
-        vec3 _normal;
-        // lighting
-        if(single_ray_df(hit_point - rd * 0.0001, sun_ray, block_id, _normal, hit_point)) {
-            return color.xyz * 0.6;
+
+        // leaving a transparent block shows the back of its face
+        uint face_block = block_id != 0 ? block_id : medium_id;
+        mat3 tbn = face_tbn(surface_normal);
+        vec3 uv_layer = parallax_uv(face_uv(face_block, surface_normal, hit_point), tbn, rd);
+        vec4 tex = get_texture(uv_layer);
+        vec3 shading_normal = get_normal(uv_layer, tbn);
+        float visibility = sun_visibility(hit_point, surface_normal);
+        vec3 lights = hit_level == 0 ? point_light(hit_point, surface_normal, shading_normal) : vec3(0.);
+        vec3 ambient = settings.accumulation != 0 ? indirect_light(hit_point, surface_normal) : ambient_light(shading_normal) * ambient_occlusion(hit_point, surface_normal, hit_level);
+
+        if(!is_transparent(face_block)) {
+            color += throughput * tex.rgb * diffuse_light(shading_normal, visibility, ambient, lights);
+            if(is_emissive(block_id)) {
+                color += throughput * tex.rgb * emission(block_id);
+            }
+            break;
         }
-        return color.xyz;
-    }
 
-    return vec3(0.5, 0.7, 0.9);
+        // the opaque parts of the texture cover the interface
+        color += throughput * tex.a * tex.rgb * diffuse_light(shading_normal, visibility, ambient, lights);
+        throughput *= 1. - tex.a;
+
+        // surface_normal faces the incoming ray, on both entering and leaving a block
+        float n1 = refractive_index(medium_id);
+        float n2 = refractive_index(block_id);
+        vec3 reflected = reflect(rd, surface_normal);
+        vec3 refracted = refract(rd, surface_normal, n1 / n2);
+        float reflectance = refracted == vec3(0.) ? 1. : fresnel(-dot(rd, surface_normal), n1, n2);
+
+        // accumulating follows either direction at random, otherwise only the sky is reflected
+        bool reflect_ray = settings.accumulation != 0 ? random() < reflectance : reflectance == 1.;
+        if(settings.accumulation == 0 && !reflect_ray) {
+            color += throughput * reflectance * sky_color(reflected);
+            throughput *= 1. - reflectance;
+        }
+        if(reflect_ray) {
+            rd = reflected;
+            ro = hit_point + surface_normal * 0.001;
+        }
+        else {
+            rd = refracted;
+            ro = hit_point - surface_normal * 0.001;
+            medium_id = block_id;
+        }
+    }
+    color = apply_fog(color, player_position, camera_rd, hit_distance, camera_block_id == WATER_BLOCK);
+    return vec4(apply_placement_preview(color, player_position, camera_rd, hit_distance), hit_distance);
 }
-
-*/
\ No newline at end of file
@@ -1,5 +1,21 @@
-const int CHUNK_SIZE = 64;
+layout (constant_id = 0) const int render_distance = 4;
+layout (constant_id = 1) const int CHUNK_SIZE = 64; // GraphicsSettings::chunk_size
+layout (constant_id = 2) const int vertical_render_distance = 4;
+const ivec3 render_distances = ivec3(render_distance, vertical_render_distance, render_distance);
+const int loaded_chunk_count = (render_distance * 2 + 1) * (vertical_render_distance * 2 + 1) * (render_distance * 2 + 1);
+layout (constant_id = 3) const int level_of_detail_layers = 1; // GraphicsSettings::level_of_detail_layers
+const int lod_chunk_count = level_of_detail_layers > 1 ? (level_of_detail_layers - 1) * loaded_chunk_count : 1;
+
 const int CHUNK_VOLUME = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+const int BRICK_SIZE = 8; // Graphics::BRICK_SIZE
+const int DISTANCE_FIELD_PADDING = 16; // Graphics::DISTANCE_FIELD_PADDING, also the largest stored distance
+const uint FAR_DISTANCE = 255; // distance_field_validation::FAR_DISTANCE, empty voxels start with it in the exact transform
+const float PI = 3.14159265359;
+const uint FIRST_TRANSPARENT_BLOCK = 4; // Block::FIRST_TRANSPARENT_ID
+const uint FIRST_EMISSIVE_BLOCK = 6; // Block::FIRST_EMISSIVE_ID
+const uint WATER_BLOCK = 5; // Block::TransparentBlock(TransparentBlock::Water)
+const uint UNIFORM_CHUNK_FLAG = 1 << 16; // chunk_info::UNIFORM_CHUNK_FLAG
+const uint BLOCK_ID_MASK = 0xFFFF;
 
 int rem_euclid_int(int dividend, int divisor) {
     return int(mod(dividend, divisor));
@@ -13,10 +29,24 @@ ivec3 rem_euclid_ivec3(ivec3 dividend, int divisor){
     );
 }
 
-vec2 chunk_AABB_test(ivec3 chunk_pos, vec3 ro, vec3 rd) {
-    vec3 bmin = vec3(chunk_pos * CHUNK_SIZE);
-    vec3 bmax = vec3((chunk_pos + ivec3(1)) * CHUNK_SIZE);
+ivec3 rem_euclid_ivec3(ivec3 dividend, ivec3 divisor){
+    return ivec3(
+        rem_euclid_int(dividend.x, divisor.x),
+        rem_euclid_int(dividend.y, divisor.y),
+        rem_euclid_int(dividend.z, divisor.z)
+    );
+}
+
+// index of the chunk image containing pos, see chunk_buffer_index
+uint chunk_storage_index(ivec3 pos) {
+    ivec3 chunks_length = render_distances * 2 + 1;
+    ivec3 storage_pos = rem_euclid_ivec3(pos, chunks_length * CHUNK_SIZE);
+
+    uvec3 chunk_address = uvec3(floor(storage_pos / CHUNK_SIZE));
+    return uint(dot(chunk_address, ivec3(1, chunks_length.x, chunks_length.x * chunks_length.y)));
+}
 
+vec2 AABB_test(vec3 bmin, vec3 bmax, vec3 ro, vec3 rd) {
     vec3 inv_rd = 1. / rd;
 
     vec3 t1 = (bmin - ro) * inv_rd;
@@ -31,6 +61,14 @@ vec2 chunk_AABB_test(ivec3 chunk_pos, vec3 ro, vec3 rd) {
     return vec2(t_enter, t_exit);
 }
 
+vec2 chunk_AABB_test(ivec3 chunk_pos, vec3 ro, vec3 rd) {
+    return AABB_test(vec3(chunk_pos * CHUNK_SIZE), vec3((chunk_pos + ivec3(1)) * CHUNK_SIZE), ro, rd);
+}
+
+vec2 brick_AABB_test(ivec3 brick_pos, vec3 ro, vec3 rd) {
+    return AABB_test(vec3(brick_pos * BRICK_SIZE), vec3((brick_pos + ivec3(1)) * BRICK_SIZE), ro, rd);
+}
+
 int argmin(vec3 args) {
     int min_index = 0;
     float min_value = args.x;
@@ -64,6 +102,38 @@ uint chebyshev_length(ivec3 pos) {
     );
 }
 
+bool is_in_bounds(ivec3 pos, int size) {
+    return all(lessThanEqual(ivec3(0), pos)) && all(lessThan(pos, ivec3(size)));
+}
+
 bool is_in_chunk_bounds(ivec3 pos) {
-    return all(lessThanEqual(ivec3(0), pos)) && all(lessThan(pos, ivec3(CHUNK_SIZE)));
-}
\ No newline at end of file
+    return is_in_bounds(pos, CHUNK_SIZE);
+}
+
+ivec3 chunk_of(ivec3 pos) {
+    return ivec3(floor(vec3(pos) / float(CHUNK_SIZE)));
+}
+
+// whether the chunk containing pos has an image while the loaded area is centered around center_chunk
+bool is_in_loaded_area(ivec3 pos, ivec3 center_chunk) {
+    return all(lessThanEqual(abs(chunk_of(pos) - center_chunk), render_distances));
+}
+
+bool is_uniform_chunk(uint info) {
+    return (info & UNIFORM_CHUNK_FLAG) != 0;
+}
+
+bool is_transparent(uint block_id) {
+    return block_id >= FIRST_TRANSPARENT_BLOCK && block_id < FIRST_EMISSIVE_BLOCK;
+}
+
+bool is_emissive(uint block_id) {
+    return block_id >= FIRST_EMISSIVE_BLOCK;
+}
+
+// pcg hash, see "Hash Functions for GPU Rendering" by Jarzynski and Olano
+uint pcg_hash(uint value) {
+    uint state = value * 747796405u + 2891336453u;
+    uint word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
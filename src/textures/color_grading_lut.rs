@@ -0,0 +1,57 @@
+use crate::settings::graphics_settings::ColorGrading;
+use nalgebra::Vector3;
+
+/// Entries of the colour grading table along each axis.
+pub const LUT_SIZE: u32 = 32;
+
+const LUMA: Vector3<f32> = Vector3::new(0.2126, 0.7152, 0.0722);
+
+/// RGBA8 entries of the 3D colour grading table with red along x, green along y and blue along z.
+/// Inputs and outputs are sRGB encoded.
+pub fn color_grading_lut(grading: &ColorGrading) -> Vec<u8> {
+    let mut lut = Vec::with_capacity((LUT_SIZE * LUT_SIZE * LUT_SIZE * 4) as usize);
+    for blue in 0..LUT_SIZE {
+        for green in 0..LUT_SIZE {
+            for red in 0..LUT_SIZE {
+                let color = Vector3::new(red, green, blue).cast::<f32>() / (LUT_SIZE - 1) as f32;
+                let [r, g, b]: [f32; 3] = grade(color, grading).into();
+                lut.extend([r, g, b, 1.].map(|channel| (channel.clamp(0., 1.) * 255.).round() as u8));
+            }
+        }
+    }
+    lut
+}
+
+fn grade(color: Vector3<f32>, grading: &ColorGrading) -> Vector3<f32> {
+    let contrasted = color.map(|channel| (channel - 0.5) * grading.contrast + 0.5);
+    let luminance = contrasted.dot(&LUMA);
+    let saturated = contrasted.map(|channel| luminance + (channel - luminance) * grading.saturation);
+    saturated.component_mul(&Vector3::new(1. + grading.temperature, 1., 1. - grading.temperature))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_neutral_lut_is_identity() {
+        let lut = color_grading_lut(&ColorGrading::NEUTRAL);
+        assert_eq!(lut.len(), (LUT_SIZE * LUT_SIZE * LUT_SIZE * 4) as usize);
+        let entry = |red: u32, green: u32, blue: u32| {
+            let index = (((blue * LUT_SIZE + green) * LUT_SIZE + red) * 4) as usize;
+            &lut[index..index + 4]
+        };
+        let value = |i: u32| (i as f32 / (LUT_SIZE - 1) as f32 * 255.).round() as u8;
+        for (red, green, blue) in [(0, 0, 0), (31, 0, 0), (0, 31, 0), (0, 0, 31), (5, 17, 29), (31, 31, 31)] {
+            assert_eq!(entry(red, green, blue), [value(red), value(green), value(blue), 255]);
+        }
+    }
+
+    #[test]
+    fn test_grading_keeps_grey_grey_without_temperature() {
+        let grading = ColorGrading { contrast: 1.3, saturation: 1.5, temperature: 0. };
+        let graded = grade(Vector3::repeat(0.25), &grading);
+        assert!((graded.x - graded.y).abs() < 1e-6 && (graded.y - graded.z).abs() < 1e-6);
+        assert!(graded.x < 0.25);
+    }
+}
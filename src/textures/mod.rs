@@ -1,6 +1,8 @@
-use crate::game_state::terrain::block::solid_block::SolidBlock;
+mod color_grading_lut;
+
 use crate::graphics::vulkano_core::VulkanoCore;
-use std::mem;
+use crate::settings::graphics_settings::ColorGrading;
+use crate::textures::color_grading_lut::{LUT_SIZE, color_grading_lut};
 use std::sync::Arc;
 use vulkano::DeviceSize;
 use vulkano::buffer::{Buffer, BufferCreateInfo, BufferUsage, Subbuffer};
@@ -13,14 +15,96 @@ use vulkano::sync::GpuFuture;
 
 const TEXTURE_SIZE: u32 = 16;
 
+/// Color textures of every block id (starting at 1) in the order top, side, bottom.
+const BLOCK_TEXTURES: [[&[u8]; 3]; 7] = [
+    [
+        include_bytes!("blocks/grass_top.png").as_slice(),
+        include_bytes!("blocks/grass_side.png").as_slice(),
+        include_bytes!("blocks/grass_bottom.png").as_slice(),
+    ],
+    [include_bytes!("blocks/stone.png").as_slice(); 3],
+    [include_bytes!("blocks/gravel.png").as_slice(); 3],
+    [include_bytes!("blocks/glass.png").as_slice(); 3],
+    [include_bytes!("blocks/water.png").as_slice(); 3],
+    [include_bytes!("blocks/lamp.png").as_slice(); 3],
+    [include_bytes!("blocks/lava.png").as_slice(); 3],
+];
+
+/// Normal maps matching `BLOCK_TEXTURES`.
+const BLOCK_NORMAL_MAPS: [[&[u8]; 3]; BLOCK_TEXTURES.len()] = [
+    [
+        include_bytes!("blocks/grass_top_normal.png").as_slice(),
+        include_bytes!("blocks/grass_side_normal.png").as_slice(),
+        include_bytes!("blocks/grass_bottom_normal.png").as_slice(),
+    ],
+    [include_bytes!("blocks/stone_normal.png").as_slice(); 3],
+    [include_bytes!("blocks/gravel_normal.png").as_slice(); 3],
+    [include_bytes!("blocks/glass_normal.png").as_slice(); 3],
+    [include_bytes!("blocks/water_normal.png").as_slice(); 3],
+    [include_bytes!("blocks/lamp_normal.png").as_slice(); 3],
+    [include_bytes!("blocks/lava_normal.png").as_slice(); 3],
+];
+
 pub fn create_block_texture_view(vulkano_core: &VulkanoCore) -> Arc<ImageView> {
-    let texture_image = create_texture_image(vulkano_core);
+    let texture_image = create_texture_image(
+        vulkano_core,
+        Format::R8G8B8A8_SRGB,
+        BLOCK_TEXTURES.as_flattened().iter().copied(),
+    );
     ImageView::new_default(texture_image).unwrap()
 }
 
-fn create_texture_image(vulkano_core: &VulkanoCore) -> Arc<Image> {
-    let format = Format::R8G8B8A8_SRGB;
-    const ARRAY_LAYERS: u32 = mem::variant_count::<SolidBlock>() as u32 * 3;
+/// Creates the tangent space normal maps of all block faces, laid out like the color textures.
+/// The alpha channel holds the height used for parallax mapping.
+pub fn create_block_normal_view(vulkano_core: &VulkanoCore) -> Arc<ImageView> {
+    let normal_image = create_texture_image(
+        vulkano_core,
+        Format::R8G8B8A8_UNORM,
+        BLOCK_NORMAL_MAPS.as_flattened().iter().copied(),
+    );
+    ImageView::new_default(normal_image).unwrap()
+}
+
+/// Creates the 3D table the colour grading pass looks the sRGB encoded colours up in.
+pub fn create_color_grading_lut_view(vulkano_core: &VulkanoCore, grading: &ColorGrading) -> Arc<ImageView> {
+    let upload_buffer = Buffer::from_iter(
+        vulkano_core.allocators.memory.clone(),
+        BufferCreateInfo {
+            usage: BufferUsage::TRANSFER_SRC,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        color_grading_lut(grading),
+    )
+    .unwrap();
+
+    let lut_image = Image::new(
+        vulkano_core.allocators.memory.clone(),
+        ImageCreateInfo {
+            image_type: ImageType::Dim3d,
+            format: Format::R8G8B8A8_UNORM,
+            extent: [LUT_SIZE; 3],
+            usage: ImageUsage::TRANSFER_DST | ImageUsage::SAMPLED,
+            ..Default::default()
+        },
+        AllocationCreateInfo::default(),
+    )
+    .unwrap();
+
+    copy_to_image(vulkano_core, upload_buffer, lut_image.clone());
+    ImageView::new_default(lut_image).unwrap()
+}
+
+fn create_texture_image<'a>(
+    vulkano_core: &VulkanoCore,
+    format: Format,
+    pngs: impl IntoIterator<Item = &'a [u8]>,
+) -> Arc<Image> {
+    const ARRAY_LAYERS: u32 = BLOCK_TEXTURES.len() as u32 * 3;
 
     let buffer_size = format.block_size()
         * (TEXTURE_SIZE * TEXTURE_SIZE) as DeviceSize
@@ -44,14 +128,7 @@ fn create_texture_image(vulkano_core: &VulkanoCore) -> Arc<Image> {
     {
         let mut image_data = &mut *upload_buffer.write().unwrap();
 
-        for png_bytes in [
-            include_bytes!("blocks/grass_top.png").as_slice(),
-            include_bytes!("blocks/grass_side.png").as_slice(),
-            include_bytes!("blocks/grass_bottom.png").as_slice(),
-            include_bytes!("blocks/glass.png").as_slice(),
-            include_bytes!("blocks/glass.png").as_slice(),
-            include_bytes!("blocks/glass.png").as_slice(),
-        ] {
+        for png_bytes in pngs {
             let decoder = png::Decoder::new(png_bytes);
             let mut reader = decoder.read_info().unwrap();
             reader.next_frame(image_data).unwrap();